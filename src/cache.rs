@@ -0,0 +1,180 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use log::{debug, warn};
+
+use crate::clone_pair::ClonePair;
+use crate::hash::StableHasher;
+
+/// The number of entries and their total size in bytes.
+#[derive(Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub total_size: u64,
+}
+
+/// A persistent store of the clone pairs keyed by the digest of the inputs of the job.
+///
+/// Each entry is a JSON file at `<root>/<first two digits of the key>/<key>.json`. The modified
+/// time of an entry is updated when it is used, so that `prune` removes the unused entries.
+pub struct ResultCache {
+    root: PathBuf,
+}
+
+impl ResultCache {
+    pub fn open(root: &Path) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(root)?;
+        Ok(ResultCache {
+            root: PathBuf::from(root),
+        })
+    }
+
+    /// Computes the key of a job from the detector settings and the contents of the sources.
+    pub fn get_key(detector_digest: &str, project_source: &[u8], example_source: &[u8]) -> String {
+        let mut hasher = StableHasher::new();
+        hasher.update_str(detector_digest);
+        hasher.update_bytes(project_source);
+        hasher.update_bytes(example_source);
+        hasher.finish_hex()
+    }
+
+    fn get_entry_path(&self, key: &str) -> PathBuf {
+        self.root.join(&key[..2]).join(format!("{}.json", key))
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<ClonePair>> {
+        let path = self.get_entry_path(key);
+        let mut file = match File::open(&path) {
+            Ok(f) => f,
+            Err(e) => {
+                if e.kind() != ErrorKind::NotFound {
+                    warn!("Could not open the cache entry: {}", e);
+                }
+                return None;
+            }
+        };
+        let mut contents = String::new();
+        if let Err(e) = file.read_to_string(&mut contents) {
+            warn!("Could not read the cache entry: {}", e);
+            return None;
+        }
+        match serde_json::from_str(&contents) {
+            Ok(pairs) => {
+                debug!("Cache hit: {}", key);
+                if let Err(e) = File::options()
+                    .write(true)
+                    .open(&path)
+                    .and_then(|f| f.set_modified(SystemTime::now()))
+                {
+                    warn!(
+                        "Could not update the modified time of the cache entry: {}",
+                        e
+                    );
+                }
+                Some(pairs)
+            }
+            Err(e) => {
+                warn!("Ignoring a broken cache entry: {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, key: &str, clone_pairs: &[ClonePair]) -> Result<(), Box<dyn Error>> {
+        let path = self.get_entry_path(key);
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir)?;
+        // NOTE: Other threads may write the same entry, so the entry is replaced atomically.
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(serde_json::to_string(clone_pairs)?.as_bytes())?;
+        file.persist(path)?;
+        Ok(())
+    }
+
+    fn list_entries(&self) -> Result<Vec<(PathBuf, fs::Metadata)>, Box<dyn Error>> {
+        let mut res = Vec::new();
+        for dir in self.root.read_dir()? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for entry in dir.path().read_dir()? {
+                let entry = entry?;
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "json") {
+                    res.push((path, entry.metadata()?));
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn get_stats(&self) -> Result<CacheStats, Box<dyn Error>> {
+        let entries = self.list_entries()?;
+        Ok(CacheStats {
+            entries: entries.len(),
+            total_size: entries.iter().map(|(_, m)| m.len()).sum(),
+        })
+    }
+
+    /// Removes the entries which have not been used for `max_age` and returns the number of them.
+    pub fn prune(&self, max_age: Duration) -> Result<usize, Box<dyn Error>> {
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for (path, metadata) in self.list_entries()? {
+            let age = now.duration_since(metadata.modified()?).unwrap_or_default();
+            if age > max_age {
+                fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Removes all entries and returns the number of them.
+    pub fn clear(&self) -> Result<usize, Box<dyn Error>> {
+        let entries = self.list_entries()?;
+        for (path, _) in &entries {
+            fs::remove_file(path)?;
+        }
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::cache::{CacheStats, ResultCache};
+    use crate::clone_pair::{ClonePair, CodePosition, CodeSlice};
+
+    #[test]
+    fn test_result_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResultCache::open(dir.path()).unwrap();
+        let key = ResultCache::get_key("CCFinderSW", b"void setup() {}", b"void loop() {}");
+        assert_ne!(
+            key,
+            ResultCache::get_key("NiCad", b"void setup() {}", b"void loop() {}")
+        );
+        assert!(cache.get(&key).is_none());
+
+        let pairs = vec![ClonePair::new(
+            CodeSlice::new(CodePosition::new(1, 0), CodePosition::new(2, 0)),
+            100.0,
+            CodeSlice::new(CodePosition::new(3, 0), CodePosition::new(4, 0)),
+            100.0,
+        )];
+        cache.put(&key, &pairs).unwrap();
+        assert_eq!(cache.get(&key), Some(pairs));
+        assert_eq!(cache.get_stats().unwrap().entries, 1);
+
+        assert_eq!(cache.prune(Duration::from_secs(3600)).unwrap(), 0);
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert_eq!(cache.get_stats().unwrap(), CacheStats::default());
+    }
+}
@@ -0,0 +1,47 @@
+use std::io;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// The signal which cancelled the run, or 0 if the run is not cancelled.
+static CANCELLED_BY: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn handle_signal(signum: libc::c_int) {
+    // NOTE: Only the async-signal-safe operations are allowed here.
+    if CANCELLED_BY.swap(signum, Ordering::SeqCst) != 0 {
+        // The second signal terminates Hugin without waiting for the running jobs.
+        // SAFETY: `_exit` is async-signal-safe.
+        unsafe { libc::_exit(128 + signum) };
+    }
+}
+
+/// Cancels the run on SIGINT and SIGTERM instead of terminating the process.
+///
+/// The detectors are spawned in their own process groups, so they don't receive the signal from
+/// the terminal and are killed by the watchdogs instead.
+pub fn install_signal_handlers() -> Result<(), io::Error> {
+    for signum in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: The handler only touches an atomic variable and calls `_exit`.
+        let res = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle_signal as extern "C" fn(libc::c_int) as usize;
+            libc::sigemptyset(&mut action.sa_mask);
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigaction(signum, &action, std::ptr::null_mut())
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED_BY.load(Ordering::SeqCst) != 0
+}
+
+/// Returns the signal which cancelled the run.
+pub fn get_signal() -> Option<i32> {
+    match CANCELLED_BY.load(Ordering::SeqCst) {
+        0 => None,
+        signum => Some(signum),
+    }
+}
@@ -1,6 +1,9 @@
-use serde_derive::Serialize;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct CodePosition {
     lines: u32,
     columns: u32,
@@ -12,7 +15,7 @@ impl CodePosition {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct CodeSlice {
     start: CodePosition,
     end: CodePosition,
@@ -22,9 +25,21 @@ impl CodeSlice {
     pub fn new(start: CodePosition, end: CodePosition) -> Self {
         CodeSlice { start, end }
     }
+
+    pub fn overlaps(&self, other: &CodeSlice) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// Returns the smallest slice covering both slices.
+    pub fn union(&self, other: &CodeSlice) -> CodeSlice {
+        CodeSlice::new(
+            self.start.clone().min(other.start.clone()),
+            self.end.clone().max(other.end.clone()),
+        )
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Scores {
     project_part: f64,
     example_sketch_part: f64,
@@ -37,15 +52,29 @@ impl Scores {
             example_sketch_part,
         }
     }
+
+    pub fn get_project_part(&self) -> f64 {
+        self.project_part
+    }
+
+    pub fn get_example_sketch_part(&self) -> f64 {
+        self.example_sketch_part
+    }
 }
 
 // NOTE: We can't store scores as bare fields (like project_score: f64) because not everything is
-// serializable into TOML format.
-#[derive(Clone, Debug, PartialEq, Serialize)]
+// serializable into TOML format. For the same reason, `detectors` must come before the tables.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ClonePair {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detectors: Option<Vec<String>>,
     project: CodeSlice,
     example_sketch: CodeSlice,
     scores: Scores,
+    /// The scores of each detector which reported the pair, since the scores of the detectors
+    /// mean different things (e.g. the number of tokens or the similarity).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detector_scores: Option<BTreeMap<String, Scores>>,
 }
 
 impl ClonePair {
@@ -56,9 +85,37 @@ impl ClonePair {
         example_sketch_score: f64,
     ) -> Self {
         ClonePair {
+            detectors: None,
             project,
             example_sketch,
             scores: Scores::new(project_score, example_sketch_score),
+            detector_scores: None,
         }
     }
+
+    /// Annotates the pair with the names of the detectors which reported it and their scores.
+    pub fn with_detectors(self, detectors: Vec<(String, Scores)>) -> Self {
+        ClonePair {
+            detectors: Some(detectors.iter().map(|(d, _)| d.clone()).collect()),
+            detector_scores: Some(detectors.into_iter().collect()),
+            ..self
+        }
+    }
+
+    pub fn get_project_part(&self) -> &CodeSlice {
+        &self.project
+    }
+
+    pub fn get_example_sketch_part(&self) -> &CodeSlice {
+        &self.example_sketch
+    }
+
+    pub fn get_scores(&self) -> &Scores {
+        &self.scores
+    }
+
+    /// Orders the pairs by their position in the project and then in the example sketch.
+    pub fn cmp_position(&self, other: &ClonePair) -> Ordering {
+        (&self.project, &self.example_sketch).cmp(&(&other.project, &other.example_sketch))
+    }
 }
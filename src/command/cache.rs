@@ -0,0 +1,34 @@
+use std::error::Error;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::ArgMatches;
+
+use hugin::{Config, InvalidConfigurationError, ResultCache};
+
+/// Shows or cleans the result cache.
+pub fn execute(config: &Config, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let cache_path = config
+        .get_cache_path()
+        .ok_or_else(|| InvalidConfigurationError::new("`cache_path` is not configured."))?;
+    let cache = ResultCache::open(&cache_path)?;
+    match matches.subcommand() {
+        ("stats", _) => {
+            let stats = cache.get_stats()?;
+            println!("Cache directory: {}", cache_path.to_str().unwrap());
+            println!("Entries: {}", stats.entries);
+            println!("Total size: {} bytes", stats.total_size);
+        }
+        ("prune", Some(m)) => {
+            let days = u64::from_str(m.value_of("DAYS").unwrap())?;
+            let removed = cache.prune(Duration::from_secs(days * 24 * 60 * 60))?;
+            println!("Removed {} entry(ies).", removed);
+        }
+        ("clear", _) => {
+            let removed = cache.clear()?;
+            println!("Removed {} entry(ies).", removed);
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
@@ -0,0 +1,587 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::ArgMatches;
+
+use log::{error, info, warn};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+use hugin::{
+    get_signal, group_jobs, install_signal_handlers, is_cancelled, Config, Job, JobResult,
+    ResultFormat, ResultWriter, Summary,
+};
+
+use crate::command::cluster::{
+    generate_token, get_token, Connection, CoordinatorMessage, ProjectFile, WorkerMessage,
+    PROTOCOL_VERSION,
+};
+use crate::command::load_session;
+
+/// How long an idle worker waits before asking for a batch again.
+const WAIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the coordinator waits for the workers to disconnect after the last result.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// What a worker asking for a batch gets.
+#[derive(Debug)]
+pub enum Assignment {
+    Batch(u64, Vec<Job>),
+    Wait,
+    Done,
+}
+
+struct Lease {
+    worker: usize,
+    jobs: Vec<Job>,
+}
+
+/// Keeps track of the batches waiting for a worker and the ones leased to the workers.
+pub struct Dispatch {
+    pending: VecDeque<Vec<Job>>,
+    leases: HashMap<u64, Lease>,
+    next_lease: u64,
+    job_ids: HashSet<String>,
+    finished: HashSet<String>,
+    workers: usize,
+}
+
+impl Dispatch {
+    pub fn new(batches: Vec<Vec<Job>>) -> Self {
+        Dispatch {
+            job_ids: batches.iter().flatten().map(|j| j.get_id()).collect(),
+            pending: VecDeque::from(batches),
+            leases: HashMap::new(),
+            next_lease: 1,
+            finished: HashSet::new(),
+            workers: 0,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.finished.len() == self.job_ids.len()
+    }
+
+    /// Leases the next batch to the worker, leaving out the jobs which already have results.
+    pub fn lease(&mut self, worker: usize) -> Assignment {
+        while let Some(batch) = self.pending.pop_front() {
+            let jobs: Vec<Job> = batch
+                .into_iter()
+                .filter(|j| !self.finished.contains(&j.get_id()))
+                .collect();
+            if jobs.is_empty() {
+                continue;
+            }
+            let lease = self.next_lease;
+            self.next_lease += 1;
+            self.leases.insert(
+                lease,
+                Lease {
+                    worker,
+                    jobs: jobs.clone(),
+                },
+            );
+            return Assignment::Batch(lease, jobs);
+        }
+        if self.is_done() {
+            Assignment::Done
+        } else {
+            Assignment::Wait
+        }
+    }
+
+    /// Ends the lease and returns the results of its jobs which had no result yet.
+    ///
+    /// The jobs of the lease without a result are dispatched again.
+    pub fn complete(&mut self, lease: u64, results: Vec<JobResult>) -> Vec<JobResult> {
+        let lease = match self.leases.remove(&lease) {
+            Some(l) => l,
+            None => return Vec::new(),
+        };
+        let leased: HashSet<String> = lease.jobs.iter().map(|j| j.get_id()).collect();
+        let new_results: Vec<JobResult> = results
+            .into_iter()
+            .filter(|r| {
+                let id = r.get_job().get_id();
+                leased.contains(&id) && self.finished.insert(id)
+            })
+            .collect();
+        let missing: Vec<Job> = lease
+            .jobs
+            .into_iter()
+            .filter(|j| !self.finished.contains(&j.get_id()))
+            .collect();
+        if !missing.is_empty() {
+            warn!("{} job(s) got no result, dispatching again.", missing.len());
+            self.pending.push_front(missing);
+        }
+        new_results
+    }
+
+    /// Puts the batches leased to the lost worker back at the front of the queue.
+    pub fn release(&mut self, worker: usize) -> usize {
+        let mut lost: Vec<u64> = self
+            .leases
+            .iter()
+            .filter(|(_, l)| l.worker == worker)
+            .map(|(id, _)| *id)
+            .collect();
+        lost.sort_unstable();
+        for id in lost.iter().rev() {
+            let lease = self.leases.remove(id).unwrap();
+            self.pending.push_front(lease.jobs);
+        }
+        lost.len()
+    }
+
+    /// Returns the jobs which have no result, e.g. to cancel them.
+    pub fn take_unfinished(&mut self) -> Vec<Job> {
+        let batches = self
+            .leases
+            .drain()
+            .map(|(_, l)| l.jobs)
+            .chain(self.pending.drain(..));
+        let mut jobs = Vec::new();
+        for job in batches.flatten() {
+            if self.finished.insert(job.get_id()) {
+                jobs.push(job);
+            }
+        }
+        jobs
+    }
+}
+
+struct Shared {
+    dispatch: Mutex<Dispatch>,
+    changed: Condvar,
+    /// Taken when the run ends.
+    writer: Mutex<Option<ResultWriter>>,
+    progress: ProgressBar,
+    lease_duration: Duration,
+    detector_digest: String,
+    token: String,
+    project_files: Vec<ProjectFile>,
+}
+
+impl Shared {
+    fn new(
+        batches: Vec<Vec<Job>>,
+        writer: ResultWriter,
+        progress: ProgressBar,
+        lease_duration: Duration,
+        detector_digest: String,
+        token: String,
+        project_files: Vec<ProjectFile>,
+    ) -> Self {
+        Shared {
+            dispatch: Mutex::new(Dispatch::new(batches)),
+            changed: Condvar::new(),
+            writer: Mutex::new(Some(writer)),
+            progress,
+            lease_duration,
+            detector_digest,
+            token,
+            project_files,
+        }
+    }
+}
+
+fn serve_worker(
+    shared: &Shared,
+    worker: usize,
+    connection: &mut Connection,
+) -> Result<(), Box<dyn Error>> {
+    match connection.receive()? {
+        Some(WorkerMessage::Hello {
+            protocol_version,
+            token,
+            detector_digest,
+            name,
+            threads,
+        }) => {
+            let refusal = if protocol_version != PROTOCOL_VERSION {
+                Some(format!(
+                    "The worker speaks the protocol version {}, but the coordinator speaks {}.",
+                    protocol_version, PROTOCOL_VERSION
+                ))
+            } else if token != shared.token {
+                Some(String::from("The token is wrong."))
+            } else if detector_digest != shared.detector_digest {
+                // NOTE: The results of different detector configurations must not be mixed.
+                Some(String::from(
+                    "The detector configuration differs from the one of the coordinator.",
+                ))
+            } else {
+                None
+            };
+            if let Some(message) = refusal {
+                connection.send(&CoordinatorMessage::Refused {
+                    message: message.clone(),
+                })?;
+                return Err(message.into());
+            }
+            info!("Worker {} joined: {} ({} thread(s))", worker, name, threads);
+        }
+        _ => return Err("The worker didn't say hello.".into()),
+    }
+    connection.send(&CoordinatorMessage::Welcome {
+        protocol_version: PROTOCOL_VERSION,
+        lease_seconds: shared.lease_duration.as_secs(),
+        project_files: shared
+            .project_files
+            .iter()
+            .map(|f| ProjectFile {
+                location: f.location.clone(),
+                contents: f.contents.clone(),
+            })
+            .collect(),
+    })?;
+
+    while let Some(message) = connection.receive()? {
+        let response = match message {
+            WorkerMessage::Lease => {
+                if is_cancelled() {
+                    CoordinatorMessage::Done
+                } else {
+                    match shared.dispatch.lock().unwrap().lease(worker) {
+                        Assignment::Batch(lease, jobs) => CoordinatorMessage::Batch { lease, jobs },
+                        Assignment::Wait => CoordinatorMessage::Wait {
+                            milliseconds: WAIT_INTERVAL.as_millis() as u64,
+                        },
+                        Assignment::Done => CoordinatorMessage::Done,
+                    }
+                }
+            }
+            WorkerMessage::Heartbeat => CoordinatorMessage::Ok,
+            WorkerMessage::Results { lease, results } => {
+                let mut dispatch = shared.dispatch.lock().unwrap();
+                let results = dispatch.complete(lease, results);
+                if let Some(writer) = shared.writer.lock().unwrap().as_ref() {
+                    for r in &results {
+                        if let Err(e) = writer.write(r) {
+                            error!("Could not write the result to the output: {}", e);
+                        }
+                    }
+                }
+                shared.progress.inc(results.len() as u64);
+                shared.changed.notify_all();
+                CoordinatorMessage::Ok
+            }
+            WorkerMessage::Hello { .. } => return Err("The worker said hello twice.".into()),
+        };
+        connection.send(&response)?;
+    }
+    Ok(())
+}
+
+fn handle_worker(shared: &Shared, worker: usize, stream: TcpStream) {
+    shared.dispatch.lock().unwrap().workers += 1;
+    // NOTE: The worker which stays silent longer than the lease is regarded as lost.
+    let res = Connection::new(stream, Some(shared.lease_duration))
+        .and_then(|mut c| serve_worker(shared, worker, &mut c));
+    let mut dispatch = shared.dispatch.lock().unwrap();
+    dispatch.workers -= 1;
+    let released = dispatch.release(worker);
+    match res {
+        Ok(()) if released == 0 => info!("Worker {} left.", worker),
+        Ok(()) => warn!(
+            "Worker {} left with {} batch(es), dispatching them again.",
+            worker, released
+        ),
+        Err(e) => warn!(
+            "Lost worker {}: {} ({} batch(es) dispatched again)",
+            worker, e, released
+        ),
+    }
+    shared.changed.notify_all();
+}
+
+/// Accepts the workers on a thread of its own, serving each of them on another thread.
+fn accept_workers(listener: TcpListener, shared: Arc<Shared>) {
+    thread::spawn(move || {
+        for (worker, stream) in listener.incoming().enumerate() {
+            match stream {
+                Ok(stream) => {
+                    let shared = shared.clone();
+                    thread::spawn(move || handle_worker(&shared, worker, stream));
+                }
+                Err(e) => warn!("Could not accept a worker: {}", e),
+            }
+        }
+    });
+}
+
+/// Waits until every job has a result or the run is cancelled, and finishes the output with the
+/// jobs left cancelled.
+fn wait_for_results(shared: &Shared) -> Result<Summary, Box<dyn Error>> {
+    // NOTE: The signals are checked periodically as they don't wake the condition variable.
+    let mut dispatch = shared.dispatch.lock().unwrap();
+    while !dispatch.is_done() && !is_cancelled() {
+        dispatch = shared
+            .changed
+            .wait_timeout(dispatch, Duration::from_millis(100))
+            .unwrap()
+            .0;
+    }
+    let writer = shared.writer.lock().unwrap().take().unwrap();
+    for job in dispatch.take_unfinished() {
+        writer.write(&job.create_cancelled_result())?;
+    }
+    shared.progress.finish();
+    // Let the workers know that the run is over.
+    let deadline = Instant::now() + SHUTDOWN_GRACE_PERIOD;
+    while dispatch.workers > 0 && Instant::now() < deadline {
+        dispatch = shared
+            .changed
+            .wait_timeout(dispatch, Duration::from_millis(100))
+            .unwrap()
+            .0;
+    }
+    drop(dispatch);
+    writer.finish()
+}
+
+/// Serves the jobs of the session to the workers and writes the results they return.
+pub fn execute(config: &Config, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let session_path = PathBuf::from_str(matches.value_of("SESSION").unwrap())?;
+    let (session, jobs) = load_session(&session_path)?;
+    let project_path = session.get_absolute_project_path(&session_path)?;
+    let locations: BTreeSet<&str> = jobs
+        .iter()
+        .map(|j| j.get_project().get_location())
+        .collect();
+    let mut project_files = Vec::new();
+    for location in locations {
+        project_files.push(ProjectFile {
+            location: String::from(location),
+            contents: fs::read(project_path.join(location))?,
+        });
+    }
+    let lease_duration =
+        Duration::from_secs(u64::from_str(matches.value_of("LEASE").unwrap_or("60"))?.max(3));
+
+    let output_filename = PathBuf::from_str(matches.value_of("OUTPUT").unwrap())?;
+    let output_format =
+        ResultFormat::from_name(matches.value_of("FORMAT").unwrap_or("toml")).unwrap();
+    let mut writer = ResultWriter::create(&output_filename, output_format)?;
+    if let Some(job_log_path) = config.get_job_log_path() {
+        writer = writer.with_job_log_path(&job_log_path)?;
+    }
+
+    let progress = ProgressBar::new(jobs.len() as u64);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("PROGRESS: {wide_bar} {pos}/{len} [{elapsed_precise}, ETA {eta}]")
+            .progress_chars("##-"),
+    );
+    let batches = group_jobs(&jobs, config.get_batch_size(), config.get_batch_by());
+    let (token, generated) = match get_token(matches) {
+        Some(t) => (t, false),
+        None => (generate_token()?, true),
+    };
+    let shared = Arc::new(Shared::new(
+        batches,
+        writer,
+        progress,
+        lease_duration,
+        config.get_detector_digest(),
+        token.clone(),
+        project_files,
+    ));
+
+    let address = matches.value_of("LISTEN").unwrap_or("127.0.0.1:8341");
+    let listener = TcpListener::bind(address)?;
+    println!(
+        "Waiting for the workers on {} ({} job(s))...",
+        listener.local_addr()?,
+        jobs.len()
+    );
+    if generated {
+        println!("The token of the workers is: {}", token);
+    }
+    install_signal_handlers()?;
+    accept_workers(listener, shared.clone());
+    let summary = wait_for_results(&shared)?;
+    println!(
+        "Finished {} job(s): {} ok, {} failed, {} timed out, {} cancelled, {} skipped.",
+        summary.total,
+        summary.ok,
+        summary.failed,
+        summary.timed_out,
+        summary.cancelled,
+        summary.skipped
+    );
+    if let Some(signum) = get_signal() {
+        warn!("The run was cancelled by signal {}.", signum);
+        std::process::exit(128 + signum);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use indicatif::ProgressBar;
+
+    use hugin::{
+        read_results, test_job, ClonePair, Job, KeepWorkDirs, ResultFormat, ResultWriter, Runner,
+        WorkDirs,
+    };
+
+    use crate::command::cluster::coordinator::{
+        accept_workers, wait_for_results, Assignment, Dispatch, Shared,
+    };
+    use crate::command::cluster::worker::{join, work};
+    use crate::command::cluster::{CoordinatorMessage, WorkerMessage, PROTOCOL_VERSION};
+
+    /// Takes longer than a lease for `A/A.ino`, so that the worker must send heartbeats.
+    struct SlowRunner;
+
+    impl Runner for SlowRunner {
+        fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+            if job.get_example_sketch().get_location() == "A/A.ino" {
+                thread::sleep(Duration::from_millis(1500));
+            }
+            Ok(Vec::new())
+        }
+    }
+
+    fn hello(token: &str) -> WorkerMessage {
+        WorkerMessage::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            token: String::from(token),
+            detector_digest: String::from("digest"),
+            name: String::from("test"),
+            threads: 1,
+        }
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let a = test_job("A/A.ino");
+        let b = test_job("B/B.ino");
+        let c = test_job("C/C.ino");
+        let mut dispatch = Dispatch::new(vec![vec![a.clone(), b.clone()], vec![c.clone()]]);
+
+        let first = match dispatch.lease(0) {
+            Assignment::Batch(lease, jobs) => {
+                assert_eq!(jobs.len(), 2);
+                lease
+            }
+            other => panic!("unexpected assignment: {:?}", other),
+        };
+        let second = match dispatch.lease(1) {
+            Assignment::Batch(lease, _) => lease,
+            other => panic!("unexpected assignment: {:?}", other),
+        };
+        assert!(matches!(dispatch.lease(2), Assignment::Wait));
+
+        // Worker 1 is lost and its batch goes to worker 2.
+        assert_eq!(dispatch.release(1), 1);
+        let third = match dispatch.lease(2) {
+            Assignment::Batch(lease, jobs) => {
+                assert_eq!(jobs[0].get_id(), c.get_id());
+                lease
+            }
+            other => panic!("unexpected assignment: {:?}", other),
+        };
+        assert!(dispatch
+            .complete(second, vec![c.create_result(Vec::new())])
+            .is_empty());
+
+        // Only `a` has a result, so `b` is dispatched again.
+        let results = dispatch.complete(first, vec![a.create_result(Vec::new())]);
+        assert_eq!(results.len(), 1);
+        match dispatch.lease(0) {
+            Assignment::Batch(_, jobs) => assert_eq!(jobs[0].get_id(), b.get_id()),
+            other => panic!("unexpected assignment: {:?}", other),
+        }
+        assert_eq!(
+            dispatch
+                .complete(third, vec![c.create_result(Vec::new())])
+                .len(),
+            1
+        );
+        assert!(!dispatch.is_done());
+        assert_eq!(dispatch.take_unfinished().len(), 1);
+        assert!(dispatch.is_done());
+        assert!(matches!(dispatch.lease(0), Assignment::Done));
+    }
+
+    #[test]
+    fn test_workers() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("result.toml");
+        let jobs: Vec<Job> = ["A/A.ino", "B/B.ino", "C/C.ino", "D/D.ino"]
+            .iter()
+            .map(|e| test_job(e))
+            .collect();
+        let shared = Arc::new(Shared::new(
+            jobs.iter().map(|j| vec![j.clone()]).collect(),
+            ResultWriter::create(&output, ResultFormat::Toml).unwrap(),
+            ProgressBar::hidden(),
+            Duration::from_secs(1),
+            String::from("digest"),
+            String::from("token"),
+            Vec::new(),
+        ));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        accept_workers(listener, shared.clone());
+
+        assert!(join(TcpStream::connect(address).unwrap(), &hello("wrong")).is_err());
+
+        // One worker closes the connection in the middle of its batch, and another one stays
+        // silent until its lease expires.
+        let (mut lost, _, _) = join(TcpStream::connect(address).unwrap(), &hello("token")).unwrap();
+        let (mut silent, _, _) =
+            join(TcpStream::connect(address).unwrap(), &hello("token")).unwrap();
+        for worker in [&mut lost, &mut silent] {
+            worker.send(&WorkerMessage::Lease).unwrap();
+            assert!(matches!(
+                worker.receive().unwrap(),
+                Some(CoordinatorMessage::Batch { .. })
+            ));
+        }
+        drop(lost);
+
+        let (connection, lease_duration, _) =
+            join(TcpStream::connect(address).unwrap(), &hello("token")).unwrap();
+        let worker = thread::spawn(move || {
+            let work_dirs = Arc::new(WorkDirs::new(None, KeepWorkDirs::Never).unwrap());
+            work(
+                connection,
+                lease_duration,
+                Arc::new(SlowRunner),
+                work_dirs,
+                2,
+            )
+        });
+        let summary = wait_for_results(&shared).unwrap();
+        assert_eq!(summary.total, 4);
+        assert_eq!(summary.ok, 4);
+        assert_eq!(worker.join().unwrap(), (4, false));
+        drop(silent);
+
+        let mut ids: Vec<String> = read_results(&output)
+            .unwrap()
+            .iter()
+            .map(|r| r.get_job().get_id())
+            .collect();
+        ids.dedup();
+        assert_eq!(ids.len(), 4);
+    }
+}
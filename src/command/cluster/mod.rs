@@ -0,0 +1,210 @@
+//! The distributed execution of a session: a coordinator leases the batches of the session to
+//! the workers connected over TCP, which run them with their own detectors.
+//!
+//! The messages are JSON objects, one per line. A worker sends `Hello` with the token of the
+//! coordinator and gets `Welcome` with the project sources, then repeats `Lease` and `Results`
+//! until it gets `Done`. Every message of the worker is answered by exactly one message of the
+//! coordinator.
+//!
+//! A lease lasts as long as the worker keeps talking: the workers send `Heartbeat` while they
+//! run the batches, and the batches of a worker which closed the connection or stayed silent for
+//! the lease duration are dispatched again.
+
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use clap::ArgMatches;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+
+use hugin::{Job, JobResult};
+
+pub mod coordinator;
+pub mod worker;
+
+/// The version of the protocol, which must be the same on the coordinator and the workers.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// The size limit of a message, which bounds the memory a peer can make the other side use.
+const MAX_MESSAGE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The environment variable holding the token, which keeps it out of the command line.
+const TOKEN_VARIABLE: &str = "HUGIN_CLUSTER_TOKEN";
+
+/// Returns the token given by `--token` or the environment variable, if any.
+pub fn get_token(matches: &ArgMatches) -> Option<String> {
+    matches
+        .value_of("TOKEN")
+        .map(String::from)
+        .or_else(|| env::var(TOKEN_VARIABLE).ok())
+}
+
+/// Returns a random token for the coordinator started without one.
+pub fn generate_token() -> io::Result<String> {
+    let mut bytes = [0; 16];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(to_hex(&bytes))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes the hexadecimal digits, or returns `None` if they are invalid or of an odd number.
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Keeps the bytes in a JSON string as hexadecimal digits.
+mod hex_bytes {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::to_hex(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::from_hex(&s).ok_or_else(|| D::Error::custom("Invalid hexadecimal digits."))
+    }
+}
+
+/// A project source of the session, which the workers write to their own project directory.
+///
+/// The contents are sent as bytes since a sketch may not be valid UTF-8.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProjectFile {
+    location: String,
+    #[serde(with = "hex_bytes")]
+    contents: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerMessage {
+    Hello {
+        protocol_version: u32,
+        /// The shared secret of the coordinator and the workers.
+        token: String,
+        /// The digest of the detector configuration of the worker (see
+        /// `Config::get_detector_digest`), which must be the same as the one of the coordinator.
+        detector_digest: String,
+        name: String,
+        threads: usize,
+    },
+    /// Asks for the next batch.
+    Lease,
+    /// Keeps the leases of the worker.
+    Heartbeat,
+    Results {
+        lease: u64,
+        results: Vec<JobResult>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CoordinatorMessage {
+    Welcome {
+        protocol_version: u32,
+        lease_seconds: u64,
+        project_files: Vec<ProjectFile>,
+    },
+    /// The worker is not accepted (e.g. the token is wrong or the detector configurations differ).
+    Refused {
+        message: String,
+    },
+    Batch {
+        lease: u64,
+        jobs: Vec<Job>,
+    },
+    /// All batches are leased to the other workers, which may be lost yet.
+    Wait {
+        milliseconds: u64,
+    },
+    /// All jobs have their results.
+    Done,
+    Ok,
+}
+
+/// A TCP connection exchanging the messages.
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    /// Wraps the stream. Reading a message fails if none arrives within `read_timeout`.
+    pub fn new(stream: TcpStream, read_timeout: Option<Duration>) -> Result<Self, Box<dyn Error>> {
+        stream.set_read_timeout(read_timeout)?;
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    pub fn send<T: Serialize>(&mut self, message: &T) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.writer, message)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_stream(self) -> TcpStream {
+        self.writer
+    }
+
+    /// Returns the next message, or `None` if the peer closed the connection.
+    pub fn receive<T: DeserializeOwned>(&mut self) -> Result<Option<T>, Box<dyn Error>> {
+        let mut line = String::new();
+        let size = (&mut self.reader)
+            .take(MAX_MESSAGE_SIZE)
+            .read_line(&mut line)
+            .map_err(|e| -> Box<dyn Error> {
+                match e.kind() {
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                        "No message arrived within the time limit.".into()
+                    }
+                    _ => e.into(),
+                }
+            })?;
+        if size == 0 {
+            return Ok(None);
+        }
+        if !line.ends_with('\n') && size as u64 == MAX_MESSAGE_SIZE {
+            return Err("The message is too large.".into());
+        }
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::command::cluster::ProjectFile;
+
+    #[test]
+    fn test_project_file() {
+        // A sketch in Shift_JIS, which is not valid UTF-8.
+        let file = ProjectFile {
+            location: String::from("MyProject/MyProject.ino"),
+            contents: vec![0x2f, 0x2f, 0x20, 0x83, 0x65, 0x83, 0x58, 0x83, 0x67, 0x0a],
+        };
+        let json = serde_json::to_string(&file).unwrap();
+        assert!(json.contains(r#""contents":"2f2f208365835883670a""#));
+        assert_eq!(serde_json::from_str::<ProjectFile>(&json).unwrap(), file);
+        assert!(
+            serde_json::from_str::<ProjectFile>(r#"{"location":"a","contents":"2f2"}"#).is_err()
+        );
+    }
+}
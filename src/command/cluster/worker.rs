@@ -0,0 +1,263 @@
+use std::error::Error;
+use std::fs;
+use std::net::TcpStream;
+use std::path::{Component, Path};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::ArgMatches;
+
+use log::{error, info, warn};
+
+use hugin::{
+    get_signal, install_signal_handlers, is_cancelled, run_jobs, ArchiveCache, Config, JobResult,
+    KeepWorkDirs, Progress, Runner, WorkDirs,
+};
+
+use crate::command::cluster::{
+    get_token, Connection, CoordinatorMessage, ProjectFile, WorkerMessage, PROTOCOL_VERSION,
+};
+use crate::command::create_session_runner;
+
+/// Collects the results of a leased batch.
+#[derive(Default)]
+struct Collector {
+    results: Mutex<Vec<JobResult>>,
+}
+
+impl Progress for Collector {
+    fn job_finished(&self, result: &JobResult) {
+        self.results.lock().unwrap().push(result.clone());
+    }
+}
+
+/// Sends the message and returns the response of the coordinator.
+fn request(
+    connection: &Mutex<Connection>,
+    message: &WorkerMessage,
+) -> Result<CoordinatorMessage, Box<dyn Error>> {
+    let mut connection = connection.lock().unwrap();
+    connection.send(message)?;
+    connection
+        .receive()?
+        .ok_or_else(|| "The coordinator closed the connection.".into())
+}
+
+fn write_project_files(project_path: &Path, files: &[ProjectFile]) -> Result<(), Box<dyn Error>> {
+    for file in files {
+        let location = Path::new(&file.location);
+        // NOTE: The sources must stay in the project directory.
+        if !location
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(format!("Invalid project source location: {}", file.location).into());
+        }
+        let path = project_path.join(location);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &file.contents)?;
+    }
+    Ok(())
+}
+
+/// Leases the batches and runs them until the coordinator has no more of them.
+fn run_thread<R>(
+    connection: &Mutex<Connection>,
+    runner: Arc<R>,
+    work_dirs: Arc<WorkDirs>,
+) -> Result<usize, Box<dyn Error>>
+where
+    R: Runner + Sync + Send + ?Sized + 'static,
+{
+    let mut number_of_batches = 0;
+    while !is_cancelled() {
+        match request(connection, &WorkerMessage::Lease)? {
+            CoordinatorMessage::Batch { lease, jobs } => {
+                let collector = Arc::new(Collector::default());
+                run_jobs(
+                    vec![jobs],
+                    runner.clone(),
+                    work_dirs.clone(),
+                    collector.clone(),
+                    1,
+                );
+                // NOTE: The cancelled jobs are left to the other workers.
+                if is_cancelled() {
+                    break;
+                }
+                let results = std::mem::take(&mut *collector.results.lock().unwrap());
+                request(connection, &WorkerMessage::Results { lease, results })?;
+                number_of_batches += 1;
+            }
+            CoordinatorMessage::Wait { milliseconds } => {
+                thread::sleep(Duration::from_millis(milliseconds))
+            }
+            CoordinatorMessage::Done => break,
+            message => return Err(format!("Unexpected message: {:?}", message).into()),
+        }
+    }
+    Ok(number_of_batches)
+}
+
+/// Says hello to the coordinator, and returns the connection with the duration of the leases and
+/// the project sources.
+pub fn join(
+    stream: TcpStream,
+    hello: &WorkerMessage,
+) -> Result<(Connection, Duration, Vec<ProjectFile>), Box<dyn Error>> {
+    let mut connection = Connection::new(stream, None)?;
+    connection.send(hello)?;
+    let (lease_duration, project_files) = match connection.receive()? {
+        Some(CoordinatorMessage::Welcome {
+            protocol_version,
+            lease_seconds,
+            project_files,
+        }) => {
+            if protocol_version != PROTOCOL_VERSION {
+                return Err(format!(
+                    "The coordinator speaks the protocol version {}, but the worker speaks {}.",
+                    protocol_version, PROTOCOL_VERSION
+                )
+                .into());
+            }
+            (Duration::from_secs(lease_seconds), project_files)
+        }
+        Some(CoordinatorMessage::Refused { message }) => return Err(message.into()),
+        _ => return Err("The coordinator didn't welcome the worker.".into()),
+    };
+    // NOTE: The coordinator answers at once, so a long silence means that it is lost.
+    let connection = Connection::new(connection.into_stream(), Some(lease_duration))?;
+    Ok((connection, lease_duration, project_files))
+}
+
+/// Runs the leased batches on the threads while sending the heartbeats, and returns the number
+/// of the finished batches and whether any thread lost the coordinator.
+pub fn work<R>(
+    connection: Connection,
+    lease_duration: Duration,
+    runner: Arc<R>,
+    work_dirs: Arc<WorkDirs>,
+    number_of_threads: usize,
+) -> (usize, bool)
+where
+    R: Runner + Sync + Send + ?Sized + 'static,
+{
+    let connection = Arc::new(Mutex::new(connection));
+    let stopped = Arc::new(AtomicBool::new(false));
+    let heartbeat = {
+        let connection = connection.clone();
+        let stopped = stopped.clone();
+        thread::spawn(move || {
+            let mut last = Instant::now();
+            while !stopped.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(100));
+                if last.elapsed() >= lease_duration / 3 {
+                    if let Err(e) = request(&connection, &WorkerMessage::Heartbeat) {
+                        warn!("Could not send a heartbeat: {}", e);
+                        return;
+                    }
+                    last = Instant::now();
+                }
+            }
+        })
+    };
+    let threads: Vec<_> = (0..number_of_threads)
+        .map(|_| {
+            let connection = connection.clone();
+            let runner = runner.clone();
+            let work_dirs = work_dirs.clone();
+            thread::spawn(move || {
+                run_thread(&connection, runner, work_dirs).map_err(|e| e.to_string())
+            })
+        })
+        .collect();
+    let mut number_of_batches = 0;
+    let mut failed = false;
+    for t in threads {
+        match t.join() {
+            Ok(Ok(n)) => number_of_batches += n,
+            Ok(Err(e)) => {
+                error!("Lost the coordinator: {}", e);
+                failed = true;
+            }
+            Err(e) => {
+                error!("A thread failed with error: {:?}", e);
+                failed = true;
+            }
+        }
+    }
+    stopped.store(true, Ordering::SeqCst);
+    heartbeat.join().unwrap();
+    (number_of_batches, failed)
+}
+
+fn get_default_name() -> String {
+    let host = fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| String::from(h.trim()))
+        .unwrap_or_else(|_| String::from("unknown"));
+    format!("{}:{}", host, std::process::id())
+}
+
+/// Runs the batches leased by the coordinator with the detector of the local configuration.
+pub fn execute(config: &Config, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let address = matches.value_of("COORDINATOR").unwrap();
+    let name = matches
+        .value_of("NAME")
+        .map(String::from)
+        .unwrap_or_else(get_default_name);
+    let token = get_token(matches).ok_or(
+        "No token: give the token printed by the coordinator with --token or HUGIN_CLUSTER_TOKEN.",
+    )?;
+    let number_of_threads = config.get_number_of_jobs().max(1);
+
+    info!("Connecting to the coordinator: {}", address);
+    let hello = WorkerMessage::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        token,
+        detector_digest: config.get_detector_digest(),
+        name: name.clone(),
+        threads: number_of_threads,
+    };
+    let (connection, lease_duration, project_files) = join(TcpStream::connect(address)?, &hello)?;
+
+    let project_dir = tempfile::Builder::new()
+        .prefix("hugin-project-")
+        .tempdir()?;
+    write_project_files(project_dir.path(), &project_files)?;
+    let archives = Arc::new(ArchiveCache::new(
+        &config.get_absolute_database_root_path()?,
+        config.get_archive_cache_size(),
+    ));
+    let work_dirs = Arc::new(WorkDirs::new(
+        config.get_scratch_path().as_deref(),
+        KeepWorkDirs::Never,
+    )?);
+    let runner = create_session_runner(config, project_dir.path(), &archives, &work_dirs)?;
+
+    install_signal_handlers()?;
+    println!(
+        "Joined the coordinator {} as {} with {} thread(s).",
+        address, name, number_of_threads
+    );
+    let (number_of_batches, failed) = work(
+        connection,
+        lease_duration,
+        runner,
+        work_dirs,
+        number_of_threads,
+    );
+
+    println!("Finished {} batch(es).", number_of_batches);
+    if let Some(signum) = get_signal() {
+        println!("Stopped by signal {}.", signum);
+        std::process::exit(128 + signum);
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
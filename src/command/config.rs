@@ -0,0 +1,14 @@
+use std::error::Error;
+
+use clap::ArgMatches;
+
+use hugin::Config;
+
+/// Prints the effective configuration, including the defaults.
+pub fn execute(config: &Config, _matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    // NOTE: Converting to a `toml::Value` sorts the keys and puts the tables last.
+    let value = toml::Value::try_from(config)?;
+    print!("{}", toml::to_string(&value)?);
+    println!("\n# detector digest: {}", config.get_detector_digest());
+    Ok(())
+}
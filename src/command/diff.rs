@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use clap::ArgMatches;
+
+use hugin::{read_results, Job, JobResult};
+
+#[derive(Debug, PartialEq)]
+enum Change {
+    Added,
+    Removed,
+    StatusChanged,
+    ClonePairsChanged,
+}
+
+struct Difference {
+    job: Job,
+    change: Change,
+    detail: String,
+}
+
+/// Compares the results of two runs job by job.
+fn diff_results(old: &[JobResult], new: &[JobResult]) -> Vec<Difference> {
+    let old_results: HashMap<String, &JobResult> =
+        old.iter().map(|r| (r.get_job().get_id(), r)).collect();
+    let new_ids: HashMap<String, ()> = new.iter().map(|r| (r.get_job().get_id(), ())).collect();
+    let mut differences = Vec::new();
+    for n in new {
+        let o = match old_results.get(&n.get_job().get_id()) {
+            Some(o) => o,
+            None => {
+                differences.push(Difference {
+                    job: n.get_job().clone(),
+                    change: Change::Added,
+                    detail: format!("{:?}", n.get_status()),
+                });
+                continue;
+            }
+        };
+        if o.get_status() != n.get_status() {
+            differences.push(Difference {
+                job: n.get_job().clone(),
+                change: Change::StatusChanged,
+                detail: format!("{:?} -> {:?}", o.get_status(), n.get_status()),
+            });
+        } else if o.get_clone_pairs() != n.get_clone_pairs() {
+            differences.push(Difference {
+                job: n.get_job().clone(),
+                change: Change::ClonePairsChanged,
+                detail: format!(
+                    "{} -> {} clone pair(s)",
+                    o.get_clone_pairs().len(),
+                    n.get_clone_pairs().len()
+                ),
+            });
+        }
+    }
+    for o in old {
+        if !new_ids.contains_key(&o.get_job().get_id()) {
+            differences.push(Difference {
+                job: o.get_job().clone(),
+                change: Change::Removed,
+                detail: format!("{:?}", o.get_status()),
+            });
+        }
+    }
+    differences
+}
+
+/// Prints the jobs whose results differ and exits with 1 if there are any, like `diff`.
+pub fn execute(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let old = read_results(Path::new(matches.value_of("OLD").unwrap()))?;
+    let new = read_results(Path::new(matches.value_of("NEW").unwrap()))?;
+    let differences = diff_results(&old, &new);
+    for d in &differences {
+        let sign = match d.change {
+            Change::Added => "+",
+            Change::Removed => "-",
+            Change::StatusChanged | Change::ClonePairsChanged => "~",
+        };
+        println!("{} {}: {}", sign, d.job, d.detail);
+    }
+    println!(
+        "{} job(s) differ between {} and {} job(s).",
+        differences.len(),
+        old.len(),
+        new.len()
+    );
+    if !differences.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::command::diff::{diff_results, Change};
+    use hugin::{test_job, ClonePair, CodePosition, CodeSlice};
+
+    #[test]
+    fn test_diff_results() {
+        let pair = ClonePair::new(
+            CodeSlice::new(CodePosition::new(1, 0), CodePosition::new(2, 0)),
+            100.0,
+            CodeSlice::new(CodePosition::new(3, 0), CodePosition::new(4, 0)),
+            100.0,
+        );
+        let old = vec![
+            test_job("A/A.ino").create_result(Vec::new()),
+            test_job("B/B.ino").create_result(Vec::new()),
+            test_job("C/C.ino").create_result(Vec::new()),
+            test_job("D/D.ino").create_result(Vec::new()),
+        ];
+        let new = vec![
+            test_job("A/A.ino").create_result(Vec::new()),
+            test_job("B/B.ino").create_skipped_result(),
+            test_job("C/C.ino").create_result(vec![pair]),
+            test_job("E/E.ino").create_result(Vec::new()),
+        ];
+        let changes: Vec<Change> = diff_results(&old, &new)
+            .into_iter()
+            .map(|d| d.change)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                Change::StatusChanged,
+                Change::ClonePairsChanged,
+                Change::Added,
+                Change::Removed
+            ]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::Path;
+
+use clap::ArgMatches;
+
+use log::info;
+
+use hugin::{read_results, Job, JobResult, JobStatus, ResultFormat, ResultWriter};
+
+use crate::command::load_session;
+
+/// Merges the results of the runs on the same session, keeping one result per job.
+///
+/// A successful result wins over the others, and otherwise the result in the later run wins.
+/// The jobs are kept in the order they first appear.
+fn merge_results(runs: Vec<Vec<JobResult>>) -> Vec<JobResult> {
+    let mut merged: Vec<JobResult> = Vec::new();
+    let mut indices: HashMap<String, usize> = HashMap::new();
+    for result in runs.into_iter().flatten() {
+        let id = result.get_job().get_id();
+        match indices.get(&id) {
+            Some(&i) => {
+                if merged[i].get_status() != JobStatus::Ok || result.get_status() == JobStatus::Ok {
+                    merged[i] = result;
+                }
+            }
+            None => {
+                indices.insert(id, merged.len());
+                merged.push(result);
+            }
+        }
+    }
+    merged
+}
+
+/// The jobs whose results are not present exactly once in the outputs of the shards.
+#[derive(Default)]
+struct ShardCheck {
+    missing: Vec<Job>,
+    duplicated: Vec<(Job, usize)>,
+    /// The results of the jobs which are not in the session.
+    unknown: Vec<Job>,
+}
+
+impl ShardCheck {
+    fn create(jobs: &[Job], runs: &[Vec<JobResult>]) -> Self {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for result in runs.iter().flatten() {
+            *counts.entry(result.get_job().get_id()).or_insert(0) += 1;
+        }
+        let mut check = ShardCheck::default();
+        for job in jobs {
+            match counts.remove(&job.get_id()) {
+                None => check.missing.push(job.clone()),
+                Some(n) if n > 1 => check.duplicated.push((job.clone(), n)),
+                Some(_) => {}
+            }
+        }
+        // NOTE: The ids left in `counts` are the jobs which are not in the session.
+        let mut seen = HashSet::new();
+        for result in runs.iter().flatten() {
+            let id = result.get_job().get_id();
+            if counts.contains_key(&id) && seen.insert(id) {
+                check.unknown.push(result.get_job().clone());
+            }
+        }
+        check
+    }
+
+    fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.duplicated.is_empty() && self.unknown.is_empty()
+    }
+
+    fn print(&self) {
+        for job in &self.missing {
+            println!("Missing: {}", job);
+        }
+        for (job, count) in &self.duplicated {
+            println!("Present {} times: {}", count, job);
+        }
+        for job in &self.unknown {
+            println!("Not in the session: {}", job);
+        }
+        println!(
+            "{} missing, {} duplicated and {} unknown job(s).",
+            self.missing.len(),
+            self.duplicated.len(),
+            self.unknown.len()
+        );
+    }
+}
+
+/// Merges the result files (e.g. of the shards or of the retried runs) into one.
+pub fn execute(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mut runs = Vec::new();
+    for input in matches.values_of("INPUTS").unwrap() {
+        info!("Reading the results: {}", input);
+        runs.push(read_results(Path::new(input))?);
+    }
+    if let Some(session_path) = matches.value_of("SESSION") {
+        let (_, jobs) = load_session(Path::new(session_path))?;
+        let check = ShardCheck::create(&jobs, &runs);
+        if !check.is_ok() {
+            check.print();
+            std::process::exit(1);
+        }
+        println!(
+            "Every job of the session is present exactly once ({} job(s)).",
+            jobs.len()
+        );
+    }
+    let merged = merge_results(runs);
+
+    let output_format =
+        ResultFormat::from_name(matches.value_of("FORMAT").unwrap_or("toml")).unwrap();
+    let writer = ResultWriter::create(
+        Path::new(matches.value_of("OUTPUT").unwrap()),
+        output_format,
+    )?;
+    for result in &merged {
+        writer.write(result)?;
+    }
+    let summary = writer.finish()?;
+    println!(
+        "Merged {} job(s): {} ok, {} failed, {} timed out, {} cancelled, {} skipped.",
+        summary.total,
+        summary.ok,
+        summary.failed,
+        summary.timed_out,
+        summary.cancelled,
+        summary.skipped
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::command::merge::{merge_results, ShardCheck};
+    use hugin::{test_job, JobStatus};
+
+    #[test]
+    fn test_merge_results() {
+        let a = test_job("A/A.ino");
+        let b = test_job("B/B.ino");
+        let merged = merge_results(vec![
+            vec![a.create_result(Vec::new()), b.create_skipped_result()],
+            vec![a.create_cancelled_result(), b.create_result(Vec::new())],
+        ]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].get_job().get_id(), a.get_id());
+        assert_eq!(merged[0].get_status(), JobStatus::Ok);
+        assert_eq!(merged[1].get_status(), JobStatus::Ok);
+    }
+
+    #[test]
+    fn test_shard_check() {
+        let a = test_job("A/A.ino");
+        let b = test_job("B/B.ino");
+        let c = test_job("C/C.ino");
+        let d = test_job("D/D.ino");
+        let check = ShardCheck::create(
+            &[a.clone(), b.clone(), c.clone()],
+            &[
+                vec![a.create_result(Vec::new()), d.create_result(Vec::new())],
+                vec![a.create_result(Vec::new()), c.create_result(Vec::new())],
+            ],
+        );
+        assert!(!check.is_ok());
+        assert_eq!(check.missing.len(), 1);
+        assert_eq!(check.missing[0].get_id(), b.get_id());
+        assert_eq!(check.duplicated.len(), 1);
+        assert_eq!(check.duplicated[0].0.get_id(), a.get_id());
+        assert_eq!(check.duplicated[0].1, 2);
+        assert_eq!(check.unknown.len(), 1);
+        assert_eq!(check.unknown[0].get_id(), d.get_id());
+
+        let check = ShardCheck::create(
+            &[a.clone(), b.clone()],
+            &[
+                vec![a.create_result(Vec::new())],
+                vec![b.create_result(Vec::new())],
+            ],
+        );
+        assert!(check.is_ok());
+    }
+}
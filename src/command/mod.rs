@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+use log::{debug, info};
+
+use hugin::{ArchiveCache, CachingRunner, Config, Job, ResultCache, Runner, Session, WorkDirs};
+
+pub mod cache;
+pub mod cluster;
+pub mod config;
+pub mod diff;
+pub mod merge;
+pub mod progress;
+pub mod report;
+pub mod run;
+pub mod serve;
+pub mod validate;
+
+/// Loads the session and all of its jobs.
+pub fn load_session(session_path: &Path) -> Result<(Session, Vec<Job>), Box<dyn Error>> {
+    info!("Loading session...");
+    let session = Session::load(session_path)?;
+    info!(
+        "The project path is: {}",
+        session
+            .get_absolute_project_path(session_path)?
+            .to_str()
+            .unwrap(),
+    );
+    info!(
+        "The jobs path is: {}",
+        session
+            .get_absolute_jobs_path(session_path)?
+            .to_str()
+            .unwrap()
+    );
+
+    let mut jobs = Vec::new();
+    for job_file in session.get_job_files(session_path)? {
+        debug!("job_file: {:?}", job_file);
+        jobs.push(Job::load(&job_file)?);
+    }
+    // NOTE: The stable sort keeps the order of the job files for the jobs with the same key.
+    jobs.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    Ok((session, jobs))
+}
+
+/// Creates the runner of the configured detector for the project, behind the result cache if it
+/// is enabled.
+pub fn create_session_runner(
+    config: &Config,
+    project_path: &Path,
+    archives: &Arc<ArchiveCache>,
+    work_dirs: &Arc<WorkDirs>,
+) -> Result<Arc<dyn Runner + Sync + Send>, Box<dyn Error>> {
+    let mut runner = hugin::create_runner(config, project_path, archives, work_dirs)?;
+    if let Some(cache_path) = config.get_cache_path() {
+        info!("Using the result cache: {}", cache_path.to_str().unwrap());
+        runner = Box::new(CachingRunner::create(
+            runner,
+            ResultCache::open(&cache_path)?,
+            config.get_cache_digest(),
+            project_path,
+            archives.clone(),
+        ));
+    }
+    Ok(Arc::from(runner))
+}
@@ -0,0 +1,99 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+
+use log::error;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+use hugin::{describe_batch, Job, JobResult, JobStatus, Journal, Progress, ResultWriter};
+
+struct Bars {
+    progress: ProgressBar,
+    statuses: Vec<ProgressBar>,
+    drawer: JoinHandle<io::Result<()>>,
+}
+
+/// Shows the progress of the run on the terminal, and writes each result to the output and
+/// records it in the journal.
+pub struct TerminalProgress {
+    journal: Arc<Journal>,
+    writer: Arc<ResultWriter>,
+    bars: Mutex<Option<Bars>>,
+}
+
+impl TerminalProgress {
+    pub fn new(journal: Arc<Journal>, writer: Arc<ResultWriter>) -> Self {
+        TerminalProgress {
+            journal,
+            writer,
+            bars: Mutex::new(None),
+        }
+    }
+
+    fn with_bars<F: FnOnce(&Bars)>(&self, f: F) {
+        if let Some(bars) = self.bars.lock().unwrap().as_ref() {
+            f(bars);
+        }
+    }
+}
+
+impl Progress for TerminalProgress {
+    fn started(&self, number_of_jobs: usize, number_of_threads: usize) {
+        let m = MultiProgress::new();
+        let progress = m.add(ProgressBar::new(number_of_jobs as u64));
+        progress.set_style(
+            ProgressStyle::default_bar()
+                .template("PROGRESS: {wide_bar} {pos}/{len} [{elapsed_precise}, ETA {eta}]")
+                .progress_chars("##-"),
+        );
+        let status_style = ProgressStyle::default_spinner().template("{prefix}: {wide_msg}");
+        let statuses = (0..number_of_threads)
+            .map(|i| {
+                let status = m.add(ProgressBar::new_spinner());
+                status.set_style(status_style.clone());
+                status.set_prefix(format!("THREAD {}", i).as_str());
+                status.set_message("waiting");
+                status
+            })
+            .collect();
+        // NOTE: `MultiProgress::join` blocks until every bar finishes, so it must be drawn on its
+        // own thread while the jobs run.
+        let drawer = thread::spawn(move || m.join());
+        *self.bars.lock().unwrap() = Some(Bars {
+            progress,
+            statuses,
+            drawer,
+        });
+    }
+
+    fn batch_started(&self, thread: usize, batch: &[Job]) {
+        self.with_bars(|b| b.statuses[thread].set_message(describe_batch(batch).as_str()));
+    }
+
+    fn job_finished(&self, result: &JobResult) {
+        if result.get_status() == JobStatus::Ok {
+            if let Err(e) = self.journal.record(result) {
+                error!("Could not record the result in the journal: {}", e);
+            }
+        }
+        if let Err(e) = self.writer.write(result) {
+            error!("Could not write the result to the output: {}", e);
+        }
+        self.with_bars(|b| b.progress.inc(1));
+    }
+
+    fn thread_finished(&self, thread: usize) {
+        self.with_bars(|b| b.statuses[thread].finish_with_message("done"));
+    }
+
+    fn finished(&self) {
+        if let Some(bars) = self.bars.lock().unwrap().take() {
+            bars.progress.finish();
+            if let Err(e) = bars.drawer.join().unwrap() {
+                error!("Could not draw the progress: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use clap::ArgMatches;
+
+use hugin::{read_results, JobErrorKind, JobResult, JobStatus, Summary};
+
+#[derive(Debug, Default, PartialEq)]
+struct LibraryStats {
+    jobs: usize,
+    jobs_with_clones: usize,
+    clone_pairs: usize,
+}
+
+/// The statistics of the results of a run.
+struct Report {
+    summary: Summary,
+    clone_pairs: usize,
+    jobs_with_clones: usize,
+    errors: BTreeMap<JobErrorKind, usize>,
+    libraries: BTreeMap<String, LibraryStats>,
+}
+
+impl Report {
+    fn create(results: &[JobResult]) -> Self {
+        let mut report = Report {
+            summary: Summary::of(results),
+            clone_pairs: 0,
+            jobs_with_clones: 0,
+            errors: BTreeMap::new(),
+            libraries: BTreeMap::new(),
+        };
+        for r in results {
+            let clone_pairs = r.get_clone_pairs().len();
+            report.clone_pairs += clone_pairs;
+            let library = report
+                .libraries
+                .entry(String::from(r.get_job().get_library_info().get_name()))
+                .or_default();
+            library.jobs += 1;
+            library.clone_pairs += clone_pairs;
+            if clone_pairs > 0 {
+                report.jobs_with_clones += 1;
+                library.jobs_with_clones += 1;
+            }
+            if let Some(e) = r.get_error() {
+                *report.errors.entry(e.get_kind()).or_insert(0) += 1;
+            }
+        }
+        report
+    }
+
+    fn print(&self, number_of_libraries: usize) {
+        let s = &self.summary;
+        println!(
+            "Jobs: {} ({} ok, {} failed, {} timed out, {} cancelled, {} skipped)",
+            s.total, s.ok, s.failed, s.timed_out, s.cancelled, s.skipped
+        );
+        println!(
+            "Clone pairs: {} in {} job(s)",
+            self.clone_pairs, self.jobs_with_clones
+        );
+        if !self.errors.is_empty() {
+            println!("Errors:");
+            for (kind, count) in &self.errors {
+                println!("  {:?}: {}", kind, count);
+            }
+        }
+        let mut libraries: Vec<(&String, &LibraryStats)> = self.libraries.iter().collect();
+        libraries.sort_by_key(|(_, s)| Reverse(s.clone_pairs));
+        println!("Libraries by the number of clone pairs:");
+        for (name, stats) in libraries.iter().take(number_of_libraries) {
+            println!(
+                "  {}: {} clone pair(s) in {} of {} job(s)",
+                name, stats.clone_pairs, stats.jobs_with_clones, stats.jobs
+            );
+        }
+        if libraries.len() > number_of_libraries {
+            println!(
+                "  ... and {} more library(ies)",
+                libraries.len() - number_of_libraries
+            );
+        }
+    }
+}
+
+/// Prints the statistics of a result file and the jobs which did not succeed.
+pub fn execute(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let results = read_results(Path::new(matches.value_of("RESULTS").unwrap()))?;
+    let number_of_libraries = usize::from_str(matches.value_of("TOP").unwrap_or("20"))?;
+    Report::create(&results).print(number_of_libraries);
+
+    let unsuccessful: Vec<&JobResult> = results
+        .iter()
+        .filter(|r| r.get_status() != JobStatus::Ok)
+        .collect();
+    if !unsuccessful.is_empty() {
+        println!("Unsuccessful jobs:");
+        for r in unsuccessful {
+            println!(
+                "  [{:?}] {}: {}",
+                r.get_status(),
+                r.get_job(),
+                r.get_error().map(|e| e.get_message()).unwrap_or("")
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::command::report::{LibraryStats, Report};
+    use hugin::{test_job, ClonePair, CodePosition, CodeSlice, JobErrorKind};
+
+    #[test]
+    fn test_create_report() {
+        let job = test_job("Example/Example.ino");
+        let pair = ClonePair::new(
+            CodeSlice::new(CodePosition::new(1, 0), CodePosition::new(2, 0)),
+            100.0,
+            CodeSlice::new(CodePosition::new(3, 0), CodePosition::new(4, 0)),
+            100.0,
+        );
+        let results = vec![
+            job.create_result(vec![pair.clone(), pair]),
+            job.create_result(Vec::new()),
+            job.create_error_result(&std::io::Error::from(std::io::ErrorKind::NotFound)),
+        ];
+        let report = Report::create(&results);
+        assert_eq!(report.summary.total, 3);
+        assert_eq!(report.clone_pairs, 2);
+        assert_eq!(report.jobs_with_clones, 1);
+        assert_eq!(report.errors.get(&JobErrorKind::Io), Some(&1));
+        assert_eq!(
+            report.libraries["Library"],
+            LibraryStats {
+                jobs: 3,
+                jobs_with_clones: 1,
+                clone_pairs: 2
+            }
+        );
+    }
+}
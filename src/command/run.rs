@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use clap::ArgMatches;
+
+use log::{error, info, warn};
+
+use hugin::{
+    get_signal, install_signal_handlers, ArchiveCache, Config, Journal, KeepWorkDirs, Plan,
+    ResultFormat, ResultWriter, Shard, WorkDirs,
+};
+
+use crate::command::progress::TerminalProgress;
+use crate::command::{create_session_runner, load_session};
+
+/// Runs the jobs of the session and writes the results.
+pub fn execute(config: &Config, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    println!(
+        "Munin database directory: {}",
+        config.get_absolute_database_root_path()?.to_str().unwrap(),
+    );
+
+    let session_path = PathBuf::from_str(matches.value_of("SESSION").unwrap())?;
+    let (session, mut jobs) = load_session(&session_path)?;
+    if let Some(shard) = matches.value_of("SHARD") {
+        let shard = Shard::from_str(shard)?;
+        let number_of_jobs = jobs.len();
+        jobs.retain(|j| shard.contains(j));
+        println!(
+            "Running shard {}: {} of {} job(s).",
+            shard,
+            jobs.len(),
+            number_of_jobs
+        );
+    }
+
+    // Resume from the journal
+    let output_filename = PathBuf::from_str(matches.value_of("OUTPUT").unwrap())?;
+    let journal_path = Journal::get_path(&output_filename);
+    let mut finished_results = Vec::new();
+    if matches.is_present("RESUME") {
+        info!(
+            "Resuming from the journal: {}",
+            journal_path.to_str().unwrap()
+        );
+        let mut recorded_results = Journal::read_results(&journal_path)?;
+        jobs.retain(|j| match recorded_results.remove(&j.get_id()) {
+            Some(res) => {
+                finished_results.push(res);
+                false
+            }
+            None => true,
+        });
+        println!(
+            "Skipping {} job(s) finished in the previous run.",
+            finished_results.len()
+        );
+    }
+
+    let number_of_jobs = config.get_number_of_jobs();
+    let project_path = session.get_absolute_project_path(&session_path)?;
+    let archives = Arc::new(ArchiveCache::new(
+        &config.get_absolute_database_root_path()?,
+        config.get_archive_cache_size(),
+    ));
+    let keep_workdirs = match matches.value_of("KEEP_WORKDIRS") {
+        Some(mode) => KeepWorkDirs::from_name(mode).unwrap(),
+        None => KeepWorkDirs::Never,
+    };
+    let work_dirs = Arc::new(WorkDirs::new(
+        config.get_scratch_path().as_deref(),
+        keep_workdirs,
+    )?);
+    let runner = create_session_runner(config, &project_path, &archives, &work_dirs)?;
+
+    let batches = hugin::group_jobs(&jobs, config.get_batch_size(), config.get_batch_by());
+    info!(
+        "Grouped {} job(s) into {} batch(es).",
+        jobs.len(),
+        batches.len()
+    );
+
+    if matches.is_present("DRY_RUN") {
+        let plan = Plan::create(
+            &format!("{:?}", config.get_clone_detector_kind()),
+            number_of_jobs,
+            &batches,
+            runner.as_ref(),
+            &project_path,
+            &archives,
+        );
+        plan.print();
+        if let Some(plan_filename) = matches.value_of("PLAN") {
+            let mut file = File::create(plan_filename)?;
+            serde_json::to_writer_pretty(&mut file, &plan)?;
+            writeln!(file)?;
+            println!("Wrote the plan to: {}", plan_filename);
+        }
+        // NOTE: The unresolved jobs fail the dry run so that scripts can check the session.
+        if plan.has_unresolved_jobs() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Open the output
+    let output_format =
+        ResultFormat::from_name(matches.value_of("FORMAT").unwrap_or("toml")).unwrap();
+    let mut writer = ResultWriter::create(&output_filename, output_format)?;
+    if let Some(job_log_path) = config.get_job_log_path() {
+        info!(
+            "Writing the logs of the failed jobs to: {}",
+            job_log_path.to_str().unwrap()
+        );
+        writer = writer.with_job_log_path(&job_log_path)?;
+    }
+    let writer = Arc::new(writer);
+
+    for res in &finished_results {
+        if let Err(e) = writer.write(res) {
+            error!("Could not write the result to the output: {}", e);
+        }
+    }
+    let journal = Arc::new(Journal::open(&journal_path, matches.is_present("RESUME"))?);
+
+    // Ctrl-C stops dispatching the jobs and kills the running detectors, and the results so far
+    // are still written.
+    install_signal_handlers()?;
+    let progress = Arc::new(TerminalProgress::new(journal, writer.clone()));
+    hugin::run_jobs(batches, runner, work_dirs, progress, number_of_jobs);
+
+    let summary = Arc::try_unwrap(writer)
+        .unwrap_or_else(|_| panic!("The output is still in use."))
+        .finish()?;
+    println!(
+        "Finished {} job(s): {} ok, {} failed, {} timed out, {} cancelled, {} skipped.",
+        summary.total,
+        summary.ok,
+        summary.failed,
+        summary.timed_out,
+        summary.cancelled,
+        summary.skipped
+    );
+    if summary.failed > 0 || summary.timed_out > 0 {
+        warn!(
+            "{} job(s) failed and {} job(s) timed out.",
+            summary.failed, summary.timed_out
+        );
+    }
+
+    if let Some(signum) = get_signal() {
+        warn!("The run was cancelled by signal {}.", signum);
+        println!("Cancelled. Run again with `--resume` to continue.");
+        std::process::exit(128 + signum);
+    }
+
+    info!("Exiting...");
+
+    Ok(())
+}
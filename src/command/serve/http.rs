@@ -0,0 +1,186 @@
+//! Just enough of HTTP/1.1 for a local API: one request per connection, no chunked bodies.
+
+use std::error::Error;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+/// The size limit of the request headers and body.
+const MAX_REQUEST_SIZE: u64 = 1024 * 1024;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn read(stream: &TcpStream) -> Result<Self, Box<dyn Error>> {
+        let mut reader = BufReader::new(stream.take(MAX_REQUEST_SIZE));
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(m), Some(t)) => (String::from(m), t),
+            _ => return Err(invalid_request("Malformed request line.")),
+        };
+        // NOTE: The query is ignored as no endpoint takes one.
+        let path = String::from(target.split('?').next().unwrap());
+
+        let mut content_length: usize = 0;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid_request("Unexpected end of the headers."));
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid_request("Invalid Content-Length."))?;
+                }
+            }
+        }
+        // NOTE: The body is allocated only after its size is checked against the limit.
+        if content_length as u64 > MAX_REQUEST_SIZE {
+            return Err(invalid_request("The request is too large."));
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        Ok(Request { method, path, body })
+    }
+
+    /// Returns the segments of the path, e.g. `["sessions", "1"]` for `/sessions/1`.
+    pub fn get_segments(&self) -> Vec<&str> {
+        self.path.split('/').filter(|s| !s.is_empty()).collect()
+    }
+}
+
+fn invalid_request(message: &str) -> Box<dyn Error> {
+    io::Error::new(io::ErrorKind::InvalidData, message).into()
+}
+
+pub struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json<T: serde::Serialize>(status: u16, value: &T) -> Self {
+        let mut body = serde_json::to_vec_pretty(value).unwrap();
+        body.push(b'\n');
+        Response {
+            status,
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Response::json(status, &serde_json::json!({ "error": message }))
+    }
+
+    pub fn file(content_type: &'static str, body: Vec<u8>) -> Self {
+        Response {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    pub fn write(&self, stream: &mut TcpStream) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            get_reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+/// Writes the head of a response whose body is streamed until the connection is closed.
+pub fn write_stream_head(stream: &mut TcpStream, content_type: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        content_type
+    )?;
+    stream.flush()
+}
+
+fn get_reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use crate::command::serve::http::{Request, Response};
+
+    #[test]
+    fn test_request_and_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(
+                stream,
+                "POST /sessions/?x=1 HTTP/1.1\r\nHost: localhost\r\ncontent-length: 7\r\n\r\n{{\"a\":1}}"
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        let request = Request::read(&stream).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.get_segments(), vec!["sessions"]);
+        assert_eq!(request.body, b"{\"a\":1}");
+        Response::error(404, "No such session.")
+            .write(&mut stream)
+            .unwrap();
+        drop(stream);
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(response.ends_with("\"error\": \"No such session.\"\n}\n"));
+    }
+
+    #[test]
+    fn test_request_too_large() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(
+                stream,
+                "POST /sessions HTTP/1.1\r\nContent-Length: 99999999999999\r\n\r\n"
+            )
+            .unwrap();
+            stream
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let _client = client.join().unwrap();
+        assert!(Request::read(&stream).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! A local HTTP API to run the sessions submitted by other programs (e.g. the Munin pipeline).
+//!
+//! - `POST /sessions` with `{"session": "<session directory>"}` queues the session.
+//! - `GET /sessions` lists the sessions and `GET /sessions/<id>` shows one of them.
+//! - `GET /sessions/<id>/progress` streams the status of the session, one JSON object per line,
+//!   whenever it changes until the session is done.
+//! - `GET /sessions/<id>/results` returns the results of a finished (or cancelled) session in the
+//!   JSON lines format of `run --format jsonl`.
+//!
+//! The results are kept in `<results dir>/<id>.jsonl`. The ids go on from the ones of the earlier
+//! runs of the server, so that their results are not overwritten.
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use clap::ArgMatches;
+
+use log::{debug, error, warn};
+
+use serde_derive::Deserialize;
+
+use hugin::{get_signal, install_signal_handlers, is_cancelled, Config};
+
+use crate::command::load_session;
+use crate::command::serve::http::{write_stream_head, Request, Response};
+use crate::command::serve::queue::{run_dispatcher, Queue};
+
+mod http;
+mod queue;
+
+/// The interval of checking whether the server is stopped while waiting for connections.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+/// The time limit of receiving a request, so that idle connections don't hold their threads.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+struct SubmitRequest {
+    session: String,
+}
+
+fn submit(queue: &Queue, body: &[u8]) -> Response {
+    let request: SubmitRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(e) => return Response::error(400, &format!("Invalid request: {}", e)),
+    };
+    let session_path = PathBuf::from(&request.session);
+    let loaded = load_session(&session_path)
+        .and_then(|(session, jobs)| Ok((session.get_absolute_project_path(&session_path)?, jobs)));
+    let (project_path, jobs) = match loaded {
+        Ok(l) => l,
+        Err(e) => return Response::error(400, &format!("Could not load the session: {}", e)),
+    };
+    match queue.add(&session_path, &project_path, jobs) {
+        Ok(status) => Response::json(201, &status),
+        Err(e) => Response::error(500, &format!("Could not queue the session: {}", e)),
+    }
+}
+
+fn get_results(queue: &Queue, id: usize) -> Response {
+    let status = match queue.get(id) {
+        Some(s) => s,
+        None => return Response::error(404, "No such session."),
+    };
+    if !status.state.is_done() {
+        return Response::error(409, "The session is not done yet.");
+    }
+    match fs::read(queue.get_results_path(id).unwrap()) {
+        Ok(contents) => Response::file("application/x-ndjson", contents),
+        Err(e) => Response::error(409, &format!("No results: {}", e)),
+    }
+}
+
+/// Writes the status of the session whenever it changes until the session is done.
+fn stream_progress(stream: &mut TcpStream, queue: &Queue, id: usize) -> io::Result<()> {
+    let mut status = match queue.get(id) {
+        Some(s) => s,
+        None => return Response::error(404, "No such session.").write(stream),
+    };
+    write_stream_head(stream, "application/x-ndjson")?;
+    loop {
+        serde_json::to_writer(&mut *stream, &status)?;
+        writeln!(stream)?;
+        stream.flush()?;
+        if status.state.is_done() {
+            return Ok(());
+        }
+        status = queue.wait_for_change(&status);
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, queue: &Queue) -> io::Result<()> {
+    let request = match Request::read(&stream) {
+        Ok(r) => r,
+        Err(e) => return Response::error(400, &e.to_string()).write(&mut stream),
+    };
+    debug!("{} {}", request.method, request.path);
+    let segments = request.get_segments();
+    let id = segments.get(1).and_then(|s| s.parse::<usize>().ok());
+    let response = match (request.method.as_str(), segments.as_slice(), id) {
+        ("POST", ["sessions"], _) => submit(queue, &request.body),
+        ("GET", ["sessions"], _) => Response::json(200, &queue.list()),
+        ("GET", ["sessions", _], Some(id)) => match queue.get(id) {
+            Some(status) => Response::json(200, &status),
+            None => Response::error(404, "No such session."),
+        },
+        ("GET", ["sessions", _, "progress"], Some(id)) => {
+            return stream_progress(&mut stream, queue, id)
+        }
+        ("GET", ["sessions", _, "results"], Some(id)) => get_results(queue, id),
+        _ => Response::error(404, "No such endpoint."),
+    };
+    response.write(&mut stream)
+}
+
+/// Serves the HTTP API until SIGINT or SIGTERM.
+pub fn execute(config: &Config, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let address = matches.value_of("LISTEN").unwrap_or("127.0.0.1:8340");
+    let results_dir = PathBuf::from(matches.value_of("RESULTS_DIR").unwrap_or("hugin-results"));
+    fs::create_dir_all(&results_dir)?;
+
+    let queue = Arc::new(Queue::open(&results_dir)?);
+    let dispatcher = {
+        let queue = queue.clone();
+        let config = config.clone();
+        thread::spawn(move || {
+            if let Err(e) = run_dispatcher(queue.clone(), config) {
+                error!("The dispatcher failed: {}", e);
+                queue.close();
+            }
+        })
+    };
+
+    let listener = TcpListener::bind(address)?;
+    // NOTE: The listener doesn't block so that the server notices the signals.
+    listener.set_nonblocking(true)?;
+    install_signal_handlers()?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    while !is_cancelled() {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                debug!("Accepted a connection from: {}", peer);
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, &queue) {
+                        debug!("The connection was closed: {}", e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+            Err(e) => warn!("Could not accept a connection: {}", e),
+        }
+    }
+
+    // The running sessions are cancelled by the signal, and the queued ones are never started.
+    queue.close();
+    dispatcher.join().unwrap();
+    if let Some(signum) = get_signal() {
+        println!("Stopped by signal {}.", signum);
+        std::process::exit(128 + signum);
+    }
+    Ok(())
+}
@@ -0,0 +1,416 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use log::{error, info};
+
+use serde_derive::Serialize;
+
+use hugin::{
+    describe_batch, group_jobs, is_cancelled, ArchiveCache, Config, Job, JobPool, JobResult,
+    KeepWorkDirs, Progress, ResultFormat, ResultWriter, Runner, Summary, WorkDirs,
+};
+
+use crate::command::create_session_runner;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    Queued,
+    Running,
+    Finished,
+    /// The session could not be run (e.g. the detector is not configured correctly).
+    Failed,
+    Cancelled,
+}
+
+impl SessionState {
+    pub fn is_done(&self) -> bool {
+        !matches!(self, SessionState::Queued | SessionState::Running)
+    }
+}
+
+/// What the API tells about a submitted session.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SessionStatus {
+    pub id: usize,
+    pub session: String,
+    pub state: SessionState,
+    pub number_of_jobs: usize,
+    /// The results so far.
+    pub summary: Summary,
+    /// The batches being checked by each thread.
+    pub running: BTreeMap<usize, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct QueuedSession {
+    status: SessionStatus,
+    project_path: PathBuf,
+    /// Taken when the session starts.
+    jobs: Vec<Job>,
+    results_path: PathBuf,
+}
+
+/// A session taken from the queue to be run.
+struct NextSession {
+    id: usize,
+    project_path: PathBuf,
+    jobs: Vec<Job>,
+    results_path: PathBuf,
+}
+
+#[derive(Default)]
+struct State {
+    sessions: Vec<QueuedSession>,
+    closed: bool,
+}
+
+/// The file in the results directory which keeps the last session id, so that the sessions of a
+/// restarted server don't overwrite the results of the earlier ones.
+const LAST_ID_FILE_NAME: &str = "last_session_id";
+
+/// The submitted sessions, which are started in the order of submission.
+pub struct Queue {
+    results_dir: PathBuf,
+    /// The id of the first session submitted to this server.
+    first_id: usize,
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Queue {
+    /// Creates the queue writing the results to `results_dir`, whose ids follow the ones of the
+    /// earlier runs of the server.
+    pub fn open(results_dir: &Path) -> io::Result<Self> {
+        let last_id = match fs::read_to_string(results_dir.join(LAST_ID_FILE_NAME)) {
+            Ok(contents) => contents.trim().parse::<usize>().map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid {}: {}", LAST_ID_FILE_NAME, e),
+                )
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        Ok(Queue {
+            results_dir: PathBuf::from(results_dir),
+            first_id: last_id + 1,
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        })
+    }
+
+    fn get_index(&self, id: usize) -> Option<usize> {
+        id.checked_sub(self.first_id)
+    }
+
+    pub fn add(
+        &self,
+        session_path: &Path,
+        project_path: &Path,
+        jobs: Vec<Job>,
+    ) -> io::Result<SessionStatus> {
+        let mut state = self.state.lock().unwrap();
+        let id = self.first_id + state.sessions.len();
+        fs::write(self.results_dir.join(LAST_ID_FILE_NAME), id.to_string())?;
+        let status = SessionStatus {
+            id,
+            session: String::from(session_path.to_str().unwrap()),
+            state: if state.closed {
+                SessionState::Cancelled
+            } else {
+                SessionState::Queued
+            },
+            number_of_jobs: jobs.len(),
+            summary: Summary::default(),
+            running: BTreeMap::new(),
+            error: None,
+        };
+        state.sessions.push(QueuedSession {
+            status: status.clone(),
+            project_path: PathBuf::from(project_path),
+            jobs,
+            results_path: self.results_dir.join(format!("{}.jsonl", id)),
+        });
+        self.changed.notify_all();
+        Ok(status)
+    }
+
+    pub fn list(&self) -> Vec<SessionStatus> {
+        let state = self.state.lock().unwrap();
+        state.sessions.iter().map(|s| s.status.clone()).collect()
+    }
+
+    pub fn get(&self, id: usize) -> Option<SessionStatus> {
+        let state = self.state.lock().unwrap();
+        state
+            .sessions
+            .get(self.get_index(id)?)
+            .map(|s| s.status.clone())
+    }
+
+    /// Blocks until the status of the session differs from `seen`.
+    pub fn wait_for_change(&self, seen: &SessionStatus) -> SessionStatus {
+        let index = self.get_index(seen.id).unwrap();
+        let state = self.state.lock().unwrap();
+        let state = self
+            .changed
+            .wait_while(state, |s| s.sessions[index].status == *seen)
+            .unwrap();
+        state.sessions[index].status.clone()
+    }
+
+    pub fn get_results_path(&self, id: usize) -> Option<PathBuf> {
+        let state = self.state.lock().unwrap();
+        state
+            .sessions
+            .get(self.get_index(id)?)
+            .map(|s| s.results_path.clone())
+    }
+
+    fn update<F: FnOnce(&mut SessionStatus)>(&self, id: usize, f: F) {
+        let mut state = self.state.lock().unwrap();
+        let index = self.get_index(id).unwrap();
+        f(&mut state.sessions[index].status);
+        self.changed.notify_all();
+    }
+
+    /// Blocks until a session is queued and marks it as running, or returns `None` once the
+    /// queue is closed.
+    fn take_next(&self) -> Option<NextSession> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.closed {
+                return None;
+            }
+            if let Some(s) = state
+                .sessions
+                .iter_mut()
+                .find(|s| s.status.state == SessionState::Queued)
+            {
+                s.status.state = SessionState::Running;
+                let next = NextSession {
+                    id: s.status.id,
+                    project_path: s.project_path.clone(),
+                    jobs: std::mem::take(&mut s.jobs),
+                    results_path: s.results_path.clone(),
+                };
+                self.changed.notify_all();
+                return Some(next);
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Stops taking the sessions and cancels the queued ones.
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        for s in &mut state.sessions {
+            if s.status.state == SessionState::Queued {
+                s.status.state = SessionState::Cancelled;
+            }
+        }
+        self.changed.notify_all();
+    }
+}
+
+/// Writes the results of a session and shows its progress in the queue.
+struct SessionProgress {
+    id: usize,
+    queue: Arc<Queue>,
+    writer: Arc<ResultWriter>,
+}
+
+impl Progress for SessionProgress {
+    fn batch_started(&self, thread: usize, batch: &[Job]) {
+        self.queue.update(self.id, |s| {
+            s.running.insert(thread, describe_batch(batch));
+        });
+    }
+
+    fn job_finished(&self, result: &JobResult) {
+        if let Err(e) = self.writer.write(result) {
+            error!("Could not write the result to the output: {}", e);
+        }
+        self.queue
+            .update(self.id, |s| s.summary.add(result.get_status()));
+    }
+
+    fn thread_finished(&self, thread: usize) {
+        self.queue.update(self.id, |s| {
+            s.running.remove(&thread);
+        });
+    }
+}
+
+/// Sets the final state of the session.
+fn finish_session(queue: &Queue, id: usize, res: Result<(), Box<dyn Error>>) {
+    queue.update(id, |s| match res {
+        Ok(()) if is_cancelled() => s.state = SessionState::Cancelled,
+        Ok(()) => s.state = SessionState::Finished,
+        Err(e) => {
+            error!("Could not run the session {}: {}", id, e);
+            s.state = SessionState::Failed;
+            s.error = Some(e.to_string());
+        }
+    });
+}
+
+/// The runners of the sessions, created once for each project.
+struct Runners {
+    config: Config,
+    archives: Arc<ArchiveCache>,
+    work_dirs: Arc<WorkDirs>,
+    runners: HashMap<PathBuf, Arc<dyn Runner + Sync + Send>>,
+}
+
+impl Runners {
+    fn get(
+        &mut self,
+        project_path: &Path,
+    ) -> Result<Arc<dyn Runner + Sync + Send>, Box<dyn Error>> {
+        if let Some(runner) = self.runners.get(project_path) {
+            return Ok(runner.clone());
+        }
+        let runner =
+            create_session_runner(&self.config, project_path, &self.archives, &self.work_dirs)?;
+        self.runners
+            .insert(PathBuf::from(project_path), runner.clone());
+        Ok(runner)
+    }
+}
+
+/// Submits the batches of the session to the pool, and returns the thread which writes its results
+/// once they are all done.
+fn start_session(
+    queue: &Arc<Queue>,
+    config: &Config,
+    pool: &JobPool,
+    runners: &mut Runners,
+    next: NextSession,
+) -> Result<thread::JoinHandle<()>, Box<dyn Error>> {
+    let runner = runners.get(&next.project_path)?;
+    let batches = group_jobs(&next.jobs, config.get_batch_size(), config.get_batch_by());
+    let mut writer = ResultWriter::create(&next.results_path, ResultFormat::JsonLines)?;
+    if let Some(job_log_path) = config.get_job_log_path() {
+        writer = writer.with_job_log_path(&job_log_path)?;
+    }
+    let writer = Arc::new(writer);
+    let progress = Arc::new(SessionProgress {
+        id: next.id,
+        queue: queue.clone(),
+        writer: writer.clone(),
+    });
+    let run = pool.submit(batches, runner, progress);
+
+    let queue = queue.clone();
+    Ok(thread::spawn(move || {
+        run.wait();
+        let res = Arc::try_unwrap(writer)
+            .unwrap_or_else(|_| panic!("The output is still in use."))
+            .finish();
+        finish_session(&queue, next.id, res.map(|_| ()));
+    }))
+}
+
+/// Runs the queued sessions until the queue is closed.
+///
+/// The sessions share the archive cache, the runners of their projects and a pool of
+/// `number_of_jobs` threads. A session starts as soon as the threads have taken all the batches of
+/// the previous one, so the threads left idle at its end go on with the next session.
+pub fn run_dispatcher(queue: Arc<Queue>, config: Config) -> Result<(), Box<dyn Error>> {
+    let archives = Arc::new(ArchiveCache::new(
+        &config.get_absolute_database_root_path()?,
+        config.get_archive_cache_size(),
+    ));
+    let work_dirs = Arc::new(WorkDirs::new(
+        config.get_scratch_path().as_deref(),
+        KeepWorkDirs::Never,
+    )?);
+    let pool = JobPool::new(config.get_number_of_jobs(), work_dirs.clone());
+    let mut runners = Runners {
+        config: config.clone(),
+        archives,
+        work_dirs,
+        runners: HashMap::new(),
+    };
+    let mut sessions = Vec::new();
+    while let Some(next) = queue.take_next() {
+        let id = next.id;
+        info!("Running the session: {}", id);
+        match start_session(&queue, &config, &pool, &mut runners, next) {
+            Ok(s) => sessions.push(s),
+            Err(e) => finish_session(&queue, id, Err(e)),
+        }
+        pool.wait_until_taken();
+    }
+    for s in sessions {
+        if s.join().is_err() {
+            error!("A session failed to write its results.");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::thread;
+
+    use tempfile::TempDir;
+
+    use crate::command::serve::queue::{Queue, SessionState};
+
+    #[test]
+    fn test_queue() {
+        let results_dir = TempDir::new().unwrap();
+        let queue = Arc::new(Queue::open(results_dir.path()).unwrap());
+        let first = queue
+            .add(Path::new("first"), Path::new("first/project"), Vec::new())
+            .unwrap();
+        queue
+            .add(Path::new("second"), Path::new("second/project"), Vec::new())
+            .unwrap();
+        assert_eq!(first.id, 1);
+        assert_eq!(
+            queue.get_results_path(2).unwrap(),
+            results_dir.path().join("2.jsonl")
+        );
+        assert!(queue.get(0).is_none() && queue.get(3).is_none());
+
+        let waiter = {
+            let queue = queue.clone();
+            thread::spawn(move || queue.wait_for_change(&first))
+        };
+        let next = queue.take_next().unwrap();
+        assert_eq!(next.id, 1);
+        assert_eq!(waiter.join().unwrap().state, SessionState::Running);
+
+        queue.update(1, |s| s.summary.add(hugin::JobStatus::Ok));
+        queue.close();
+        assert!(queue.take_next().is_none());
+        let states: Vec<SessionState> = queue.list().iter().map(|s| s.state).collect();
+        assert_eq!(states, vec![SessionState::Running, SessionState::Cancelled]);
+        assert_eq!(queue.get(1).unwrap().summary.ok, 1);
+
+        // The ids of a restarted server go on from the earlier ones.
+        let queue = Queue::open(results_dir.path()).unwrap();
+        assert!(queue.get(1).is_none());
+        let third = queue
+            .add(Path::new("third"), Path::new("third/project"), Vec::new())
+            .unwrap();
+        assert_eq!(third.id, 3);
+        assert_eq!(
+            queue.get_results_path(3).unwrap(),
+            results_dir.path().join("3.jsonl")
+        );
+    }
+}
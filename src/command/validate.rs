@@ -0,0 +1,18 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::ArgMatches;
+
+use hugin::{validate_session, Config};
+
+/// Checks the session and exits with 1 if there are problems.
+pub fn execute(config: &Config, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let session_path = PathBuf::from_str(matches.value_of("SESSION").unwrap())?;
+    let report = validate_session(&session_path, config);
+    report.print();
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -26,12 +26,98 @@ fn deserialize_language(s: &str) -> Result<Languages, ()> {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct WorkerConfig {
+    java_path: String,
+    launcher_path: PathBuf,
+    classpath: String,
+    main_class: String,
+}
+
+impl WorkerConfig {
+    fn from_hashmap(
+        hashmap: &HashMap<String, String>,
+    ) -> Result<Option<Self>, InvalidConfigurationError> {
+        let enabled = match hashmap.get("worker") {
+            Some(s) => bool::from_str(s)
+                .map_err(|_| InvalidConfigurationError::new("Invalid value for `worker`"))?,
+            None => false,
+        };
+        if !enabled {
+            return Ok(None);
+        }
+        let java_path = hashmap
+            .get("worker_java")
+            .cloned()
+            .unwrap_or_else(|| String::from("java"));
+        let launcher_path = PathBuf::from(
+            shellexpand::tilde(hashmap.get("worker_launcher_path").ok_or_else(|| {
+                InvalidConfigurationError::new("Missing key: `worker_launcher_path`")
+            })?)
+            .as_ref(),
+        )
+        .canonicalize()
+        .map_err(|_| {
+            InvalidConfigurationError::new("Could not canonicalize the specified path.")
+        })?;
+        let classpath = hashmap
+            .get("worker_classpath")
+            .ok_or_else(|| InvalidConfigurationError::new("Missing key: `worker_classpath`"))?
+            .split(':')
+            .map(|p| String::from(shellexpand::tilde(p).as_ref()))
+            .collect::<Vec<String>>()
+            .join(":");
+        let main_class = hashmap
+            .get("worker_main_class")
+            .ok_or_else(|| InvalidConfigurationError::new("Missing key: `worker_main_class`"))?
+            .clone();
+
+        Ok(Some(WorkerConfig {
+            java_path,
+            launcher_path,
+            classpath,
+            main_class,
+        }))
+    }
+
+    pub fn get_java_path(&self) -> &str {
+        self.java_path.as_str()
+    }
+
+    /// Returns the arguments to launch the worker with `java`.
+    pub fn to_java_arguments(&self) -> Vec<String> {
+        vec![
+            String::from("-cp"),
+            format!(
+                "{}:{}",
+                self.launcher_path.to_str().unwrap(),
+                self.classpath
+            ),
+            String::from("HuginWorker"),
+            self.main_class.clone(),
+        ]
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct CCFinderSWConfig {
     executable_path: PathBuf,
     token_length: u32,
     language: Languages,
     extensions: Vec<String>,
+    worker: Option<WorkerConfig>,
+}
+
+impl Default for CCFinderSWConfig {
+    fn default() -> Self {
+        CCFinderSWConfig {
+            executable_path: PathBuf::from("CCFinderSW"),
+            token_length: 50,
+            language: Languages::CPlusPlus,
+            extensions: Vec::from([String::from("pde"), String::from("ino")]),
+            worker: None,
+        }
+    }
 }
 
 impl CCFinderSWConfig {
@@ -49,15 +135,6 @@ impl CCFinderSWConfig {
         }
     }
 
-    pub fn default() -> Self {
-        CCFinderSWConfig {
-            executable_path: PathBuf::from("CCFinderSW"),
-            token_length: 50,
-            language: Languages::CPlusPlus,
-            extensions: Vec::from([String::from("pde"), String::from("ino")]),
-        }
-    }
-
     pub fn to_hashmap(&self) -> HashMap<String, String> {
         [
             (
@@ -105,11 +182,14 @@ impl CCFinderSWConfig {
             .map(|s| String::from(s))
             .collect();
 
+        let worker = WorkerConfig::from_hashmap(hashmap)?;
+
         Ok(CCFinderSWConfig {
             executable_path,
             token_length,
             language,
             extensions,
+            worker,
         })
     }
 
@@ -130,4 +210,8 @@ impl CCFinderSWConfig {
     pub fn extensions_to_option_value(&self) -> String {
         self.extensions.join("|")
     }
+
+    pub fn get_worker_config(&self) -> Option<&WorkerConfig> {
+        self.worker.as_ref()
+    }
 }
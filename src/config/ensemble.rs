@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use log::error;
+
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+
+use crate::config::{CloneDetectorKind, Config};
+use crate::error::InvalidConfigurationError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Quorum {
+    Any,
+    Majority,
+    All,
+}
+
+fn deserialize_quorum(s: &str) -> Result<Quorum, ()> {
+    match s {
+        "any" => Ok(Quorum::Any),
+        "majority" => Ok(Quorum::Majority),
+        "all" => Ok(Quorum::All),
+        _ => Err(()),
+    }
+}
+
+impl Quorum {
+    /// Returns the number of detectors which must agree on a clone pair.
+    pub fn get_required_votes(&self, number_of_detectors: usize) -> usize {
+        match self {
+            Quorum::Any => 1,
+            Quorum::Majority => number_of_detectors / 2 + 1,
+            Quorum::All => number_of_detectors,
+        }
+    }
+}
+
+/// The configuration of the ensemble of detectors.
+///
+/// The configuration of each member is given by the keys prefixed with the name of its kind,
+/// e.g. `"Native.token_length" = "50"`.
+#[derive(Clone)]
+pub struct EnsembleConfig {
+    members: Vec<(String, Config)>,
+    quorum: Quorum,
+}
+
+impl EnsembleConfig {
+    pub fn try_from_config(config: &Config) -> Option<Self> {
+        if config.clone_detector_kind != CloneDetectorKind::Ensemble {
+            None
+        } else {
+            match EnsembleConfig::from_config(config) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    error!("Invalid configuration: {:?}", e);
+                    None
+                }
+            }
+        }
+    }
+
+    fn from_config(config: &Config) -> Result<Self, InvalidConfigurationError> {
+        let hashmap = &config.clone_detector_config;
+        let mut members = Vec::new();
+        for name in hashmap
+            .get("members")
+            .ok_or_else(|| InvalidConfigurationError::new("Missing key: `members`"))?
+            .split(',')
+            .map(|s| s.trim())
+        {
+            let kind = CloneDetectorKind::deserialize(name.into_deserializer()).map_err(
+                |_: serde::de::value::Error| {
+                    InvalidConfigurationError::new("Invalid value for `members`")
+                },
+            )?;
+            if kind == CloneDetectorKind::Ensemble || members.iter().any(|(n, _)| n == name) {
+                return Err(InvalidConfigurationError::new(
+                    "Invalid value for `members`",
+                ));
+            }
+            let prefix = format!("{}.", name);
+            let member_config: HashMap<String, String> = hashmap
+                .iter()
+                .filter_map(|(k, v)| {
+                    k.strip_prefix(prefix.as_str())
+                        .map(|k| (String::from(k), v.clone()))
+                })
+                .collect();
+            members.push((
+                String::from(name),
+                config.with_clone_detector(kind, member_config),
+            ));
+        }
+        if members.is_empty() {
+            return Err(InvalidConfigurationError::new(
+                "Invalid value for `members`",
+            ));
+        }
+        let quorum = deserialize_quorum(
+            hashmap
+                .get("quorum")
+                .ok_or_else(|| InvalidConfigurationError::new("Missing key: `quorum`"))?,
+        )
+        .map_err(|_| InvalidConfigurationError::new("Invalid value for `quorum`"))?;
+
+        Ok(EnsembleConfig { members, quorum })
+    }
+
+    pub fn get_members(&self) -> &Vec<(String, Config)> {
+        &self.members
+    }
+
+    pub fn get_quorum(&self) -> &Quorum {
+        &self.quorum
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use log::error;
+
+use crate::config::{CloneDetectorKind, Config};
+use crate::error::InvalidConfigurationError;
+
+/// The placeholders of the working directory layout, filled with the names of the job.
+pub const LAYOUT_VARIABLES: &[&str] = &["project_name", "example_name", "library_name"];
+
+/// The placeholders of the command line filled by the runner for every job, in addition to the
+/// layout ones.
+pub const JOB_VARIABLES: &[&str] = &[
+    "working_dir",
+    "sources_dir",
+    "project_file",
+    "example_file",
+    "output_file",
+];
+
+/// Returns the variables with empty values, to check the templates before any job runs.
+fn get_dummy_variables(names: &[&[&str]]) -> HashMap<String, String> {
+    names
+        .iter()
+        .flat_map(|n| n.iter())
+        .map(|v| (String::from(*v), String::new()))
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    Ccfx,
+    Json,
+    Csv,
+}
+
+fn deserialize_output_format(s: &str) -> Result<OutputFormat, ()> {
+    match s {
+        "ccfx" => Ok(OutputFormat::Ccfx),
+        "json" => Ok(OutputFormat::Json),
+        "csv" => Ok(OutputFormat::Csv),
+        _ => Err(()),
+    }
+}
+
+/// Replaces every `{name}` in the template with the value of the variable.
+///
+/// `{{` and `}}` are the escaped braces.
+pub fn expand_template(
+    template: &str,
+    variables: &HashMap<String, String>,
+) -> Result<String, InvalidConfigurationError> {
+    let mut res = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                res.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                res.push('}');
+            }
+            '{' => {
+                let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let value = variables.get(&name).ok_or_else(|| {
+                    InvalidConfigurationError::new(
+                        format!("Unknown placeholder in template: `{{{}}}`", name).as_str(),
+                    )
+                })?;
+                res.push_str(value);
+            }
+            '}' => return Err(InvalidConfigurationError::new("Unmatched `}` in template.")),
+            _ => res.push(c),
+        }
+    }
+    Ok(res)
+}
+
+/// Where the sources of a job are staged and where the detector writes its output, relative to
+/// the working directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub sources_dir: String,
+    pub project_source: String,
+    pub example_source: String,
+    pub output_file: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExternalConfig {
+    command: Vec<String>,
+    sources_dir: String,
+    project_source: String,
+    example_source: String,
+    output_file: String,
+    output_format: OutputFormat,
+    variables: HashMap<String, String>,
+}
+
+impl ExternalConfig {
+    pub fn try_from_config(config: &Config) -> Option<Self> {
+        if config.clone_detector_kind != CloneDetectorKind::External {
+            None
+        } else {
+            match ExternalConfig::from_hashmap(&config.clone_detector_config) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    error!("Invalid configuration: {:?}", e);
+                    None
+                }
+            }
+        }
+    }
+
+    fn from_hashmap(hashmap: &HashMap<String, String>) -> Result<Self, InvalidConfigurationError> {
+        let command: Vec<String> = hashmap
+            .get("command")
+            .ok_or_else(|| InvalidConfigurationError::new("Missing key: `command`"))?
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        if command.is_empty() {
+            return Err(InvalidConfigurationError::new(
+                "Invalid value for `command`",
+            ));
+        }
+        let sources_dir = hashmap
+            .get("sources_dir")
+            .cloned()
+            .unwrap_or_else(|| String::from("src"));
+        let project_source = hashmap
+            .get("project_source")
+            .cloned()
+            .unwrap_or_else(|| String::from("{project_name}"));
+        let example_source = hashmap
+            .get("example_source")
+            .cloned()
+            .unwrap_or_else(|| String::from("{example_name}"));
+        let output_file = hashmap
+            .get("output_file")
+            .cloned()
+            .unwrap_or_else(|| String::from("result.txt"));
+        let output_format = deserialize_output_format(
+            hashmap
+                .get("output_format")
+                .ok_or_else(|| InvalidConfigurationError::new("Missing key: `output_format`"))?,
+        )
+        .map_err(|_| InvalidConfigurationError::new("Invalid value for `output_format`"))?;
+
+        let config = ExternalConfig {
+            command,
+            sources_dir,
+            project_source,
+            example_source,
+            output_file,
+            output_format,
+            variables: hashmap.clone(),
+        };
+        // Check the templates with dummy values so that errors are reported before any job runs.
+        let dummy = get_dummy_variables(&[LAYOUT_VARIABLES, JOB_VARIABLES]);
+        config.expand_layout(&dummy)?;
+        config.expand_command(&dummy)?;
+        Ok(config)
+    }
+
+    /// Returns the program run by the command line.
+    pub fn get_program(&self) -> String {
+        let dummy = get_dummy_variables(&[LAYOUT_VARIABLES, JOB_VARIABLES]);
+        // NOTE: The command line was checked in `from_hashmap`.
+        self.expand_command(&dummy).unwrap().remove(0)
+    }
+
+    /// Expands the layout templates with the layout variables and the configuration entries.
+    pub fn expand_layout(
+        &self,
+        layout_variables: &HashMap<String, String>,
+    ) -> Result<Layout, InvalidConfigurationError> {
+        let mut variables = self.variables.clone();
+        variables.extend(layout_variables.clone());
+        Ok(Layout {
+            sources_dir: expand_template(&self.sources_dir, &variables)?,
+            project_source: expand_template(&self.project_source, &variables)?,
+            example_source: expand_template(&self.example_source, &variables)?,
+            output_file: expand_template(&self.output_file, &variables)?,
+        })
+    }
+
+    /// Expands the command line with the job variables and the configuration entries.
+    pub fn expand_command(
+        &self,
+        job_variables: &HashMap<String, String>,
+    ) -> Result<Vec<String>, InvalidConfigurationError> {
+        let mut variables = self.variables.clone();
+        variables.extend(job_variables.clone());
+        let mut res = Vec::new();
+        for (i, arg) in self.command.iter().enumerate() {
+            let arg = expand_template(arg, &variables)?;
+            if i == 0 {
+                res.push(String::from(shellexpand::tilde(&arg).as_ref()));
+            } else {
+                res.push(arg);
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn get_output_format(&self) -> &OutputFormat {
+        &self.output_format
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::config::external::{expand_template, ExternalConfig, Layout};
+
+    #[test]
+    fn test_expand_template() {
+        let variables: HashMap<String, String> = [
+            (String::from("sources_dir"), String::from("src")),
+            (String::from("token_length"), String::from("50")),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        assert_eq!(
+            expand_template("--in={sources_dir} -t {token_length} {{x}}", &variables).unwrap(),
+            "--in=src -t 50 {x}"
+        );
+        assert!(expand_template("{unknown}", &variables).is_err());
+        assert!(expand_template("a}b", &variables).is_err());
+    }
+
+    #[test]
+    fn test_expand_layout() {
+        let hashmap: HashMap<String, String> = [
+            ("command", "my-detector {project_file} {example_file}"),
+            ("sources_dir", "in/{library_name}"),
+            ("example_source", "{example_name}.{extension}"),
+            ("output_file", "{example_name}.csv"),
+            ("output_format", "csv"),
+            ("extension", "cpp"),
+        ]
+        .iter()
+        .map(|(k, v)| (String::from(*k), String::from(*v)))
+        .collect();
+        let config = ExternalConfig::from_hashmap(&hashmap).unwrap();
+        let variables: HashMap<String, String> = [
+            ("project_name", "MyProject.ino"),
+            ("example_name", "Example.ino"),
+            ("library_name", "Library"),
+        ]
+        .iter()
+        .map(|(k, v)| (String::from(*k), String::from(*v)))
+        .collect();
+        assert_eq!(
+            config.expand_layout(&variables).unwrap(),
+            Layout {
+                sources_dir: String::from("in/Library"),
+                project_source: String::from("MyProject.ino"),
+                example_source: String::from("Example.ino.cpp"),
+                output_file: String::from("Example.ino.csv"),
+            }
+        );
+
+        let mut invalid = hashmap;
+        invalid.insert(String::from("output_file"), String::from("{unknown}.csv"));
+        assert!(ExternalConfig::from_hashmap(&invalid).is_err());
+    }
+}
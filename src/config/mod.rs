@@ -1,35 +1,256 @@
 use std::collections::HashMap;
 use std::error::Error;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::config::ccfindersw::CCFinderSWConfig;
+use crate::hash::StableHasher;
+use crate::job::{Job, LibraryInfo};
 
 pub mod ccfindersw;
+pub mod ensemble;
+pub mod external;
+pub mod native;
+pub mod nicad;
+pub mod plugin;
 
-#[derive(Clone, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum CloneDetectorKind {
     CCFinderSW,
+    Native,
+    NiCad,
+    External,
+    Ensemble,
+    Plugin,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum BatchKey {
+    Project,
+    Library,
+}
+
+/// The entries of `clone_detector_config` which tell where the detector is installed and how it
+/// is started, but not which clone pairs it finds.
+const HOST_LOCAL_KEYS: [&str; 6] = [
+    "executable_path",
+    "worker",
+    "worker_java",
+    "worker_classpath",
+    "worker_launcher_path",
+    "worker_main_class",
+];
+
+/// Returns the name of the entry without the prefix of an ensemble member (e.g.
+/// `NiCad.executable_path`).
+fn get_entry_name(key: &str) -> &str {
+    key.rsplit('.').next().unwrap()
+}
+
+fn default_batch_size() -> usize {
+    1
+}
+
+fn default_batch_key() -> BatchKey {
+    BatchKey::Project
+}
+
+fn default_archive_cache_size() -> usize {
+    64
+}
+
+/// The time limits of the detector processes in seconds.
+#[derive(Clone, Debug, Default)]
+pub struct JobTimeouts {
+    default: Option<Duration>,
+    libraries: HashMap<String, Duration>,
+}
+
+impl JobTimeouts {
+    /// Returns the time limit of the jobs on the library, or `None` if there is no limit.
+    pub fn get_timeout(&self, library_info: &LibraryInfo) -> Option<Duration> {
+        self.libraries
+            .get(library_info.get_name())
+            .cloned()
+            .or(self.default)
+    }
+
+    /// Returns the time limit of a detector process checking all jobs at once.
+    ///
+    /// Each job keeps its own share of the limit, so the limit of the batch is the sum of them.
+    pub fn get_batch_timeout(&self, jobs: &[Job]) -> Option<Duration> {
+        jobs.iter()
+            .map(|j| self.get_timeout(j.get_library_info()))
+            .sum()
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Config {
     munin_database_root: String,
     clone_detector_kind: CloneDetectorKind,
     pub(crate) number_of_jobs: usize,
+    #[serde(default = "default_batch_size")]
+    pub(crate) batch_size: usize,
+    #[serde(default = "default_batch_key")]
+    pub(crate) batch_by: BatchKey,
+    job_timeout: Option<u64>,
+    #[serde(default)]
+    library_timeouts: HashMap<String, u64>,
+    cache_path: Option<String>,
+    job_log_path: Option<String>,
+    scratch_path: Option<String>,
+    #[serde(default = "default_archive_cache_size")]
+    archive_cache_size: usize,
     clone_detector_config: HashMap<String, String>,
 }
 
-impl Config {
-    pub fn default() -> Self {
+impl Default for Config {
+    fn default() -> Self {
         Config {
             munin_database_root: String::from("~/munin"),
             clone_detector_kind: CloneDetectorKind::CCFinderSW,
             number_of_jobs: 1,
+            batch_size: default_batch_size(),
+            batch_by: default_batch_key(),
+            job_timeout: None,
+            library_timeouts: HashMap::new(),
+            cache_path: None,
+            job_log_path: None,
+            scratch_path: None,
+            archive_cache_size: default_archive_cache_size(),
             clone_detector_config: CCFinderSWConfig::default().to_hashmap(),
         }
     }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(toml::from_str(contents.as_str())?)
+    }
+
+    /// Creates the configuration for another detector which shares the other settings.
+    pub fn with_clone_detector(
+        &self,
+        clone_detector_kind: CloneDetectorKind,
+        clone_detector_config: HashMap<String, String>,
+    ) -> Self {
+        Config {
+            clone_detector_kind,
+            clone_detector_config,
+            ..self.clone()
+        }
+    }
+
+    pub fn get_clone_detector_kind(&self) -> &CloneDetectorKind {
+        &self.clone_detector_kind
+    }
+
+    pub fn get_number_of_jobs(&self) -> usize {
+        self.number_of_jobs
+    }
+
+    pub fn get_batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    pub fn get_batch_by(&self) -> &BatchKey {
+        &self.batch_by
+    }
+
+    pub fn get_job_timeouts(&self) -> JobTimeouts {
+        JobTimeouts {
+            default: self.job_timeout.map(Duration::from_secs),
+            libraries: self
+                .library_timeouts
+                .iter()
+                .map(|(name, t)| (name.clone(), Duration::from_secs(*t)))
+                .collect(),
+        }
+    }
+
+    /// Returns the directory of the result cache, or `None` if the cache is disabled.
+    pub fn get_cache_path(&self) -> Option<PathBuf> {
+        self.cache_path
+            .as_ref()
+            .map(|p| PathBuf::from(shellexpand::tilde(p.as_str()).as_ref()))
+    }
+
+    /// Returns the directory of the logs of the failed jobs, or `None` if they are not written.
+    pub fn get_job_log_path(&self) -> Option<PathBuf> {
+        self.job_log_path
+            .as_ref()
+            .map(|p| PathBuf::from(shellexpand::tilde(p.as_str()).as_ref()))
+    }
+
+    /// Returns the directory where the working directories of the detectors are created, or
+    /// `None` to use the temporary directory of the system.
+    pub fn get_scratch_path(&self) -> Option<PathBuf> {
+        self.scratch_path
+            .as_ref()
+            .map(|p| PathBuf::from(shellexpand::tilde(p.as_str()).as_ref()))
+    }
+
+    /// Returns the size limit of the example sketches kept in memory in bytes.
+    pub fn get_archive_cache_size(&self) -> usize {
+        self.archive_cache_size * 1024 * 1024
+    }
+
+    /// Returns the digest of the settings which affect the clone pairs found by the detector.
+    ///
+    /// The settings which depend on the host, e.g. where the detector is installed, are left out so
+    /// that the workers of a cluster may install it elsewhere. The version of Hugin is included
+    /// because the defaults of the settings may change.
+    pub fn get_detector_digest(&self) -> String {
+        let mut hasher = StableHasher::new();
+        hasher.update_str(env!("CARGO_PKG_VERSION"));
+        hasher.update_str(format!("{:?}", self.clone_detector_kind).as_str());
+        let mut entries: Vec<(&String, &String)> = self.clone_detector_config.iter().collect();
+        entries.sort();
+        for (k, v) in entries {
+            let name = get_entry_name(k);
+            if HOST_LOCAL_KEYS.contains(&name) {
+                continue;
+            }
+            hasher.update_str(k);
+            if name == "command" {
+                // NOTE: Only the arguments are hashed since the program is a path on the host.
+                for arg in v.split_whitespace().skip(1) {
+                    hasher.update_str(arg);
+                }
+            } else {
+                hasher.update_str(v);
+            }
+        }
+        hasher.finish_hex()
+    }
+
+    /// Returns the digest of the settings which affect the cached clone pairs.
+    ///
+    /// Unlike `get_detector_digest`, the whole detector configuration is included since another
+    /// installation of the detector may be another version. The batching is included because the
+    /// detectors checking many files at once (e.g. CCFinderSW) may find other pairs than when
+    /// checking a job alone.
+    pub fn get_cache_digest(&self) -> String {
+        let mut hasher = StableHasher::new();
+        hasher.update_str(&self.get_detector_digest());
+        let mut entries: Vec<(&String, &String)> = self.clone_detector_config.iter().collect();
+        entries.sort();
+        for (k, v) in entries {
+            hasher.update_str(k);
+            hasher.update_str(v);
+        }
+        hasher.update_str(&self.batch_size.to_string());
+        hasher.update_str(format!("{:?}", self.batch_by).as_str());
+        hasher.finish_hex()
+    }
 
     pub fn get_absolute_database_root_path(&self) -> Result<PathBuf, Box<dyn Error>> {
         Ok(
@@ -38,3 +259,36 @@ impl Config {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+
+    fn create_config(executable_path: &str, token_length: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+munin_database_root = "~/munin"
+clone_detector_kind = "CCFinderSW"
+number_of_jobs = 1
+
+[clone_detector_config]
+executable_path = "{}"
+token_length = "{}"
+language = "CPlusPlus"
+"#,
+            executable_path, token_length
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_detector_digest() {
+        let config = create_config("/opt/CCFinderSW/bin/CCFinderSW", "50");
+        let moved = create_config("~/tools/CCFinderSW/bin/CCFinderSW", "50");
+        let longer = create_config("/opt/CCFinderSW/bin/CCFinderSW", "60");
+        assert_eq!(config.get_detector_digest(), moved.get_detector_digest());
+        assert_ne!(config.get_detector_digest(), longer.get_detector_digest());
+        // The cached pairs may come from another version of the detector installed elsewhere.
+        assert_ne!(config.get_cache_digest(), moved.get_cache_digest());
+    }
+}
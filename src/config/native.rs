@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use log::error;
+
+use crate::config::{CloneDetectorKind, Config};
+use crate::error::InvalidConfigurationError;
+
+#[derive(Clone, Debug)]
+pub struct NativeConfig {
+    token_length: u32,
+    normalize: bool,
+}
+
+impl NativeConfig {
+    pub fn try_from_config(config: &Config) -> Option<Self> {
+        if config.clone_detector_kind != CloneDetectorKind::Native {
+            None
+        } else {
+            match NativeConfig::from_hashmap(&config.clone_detector_config) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    error!("Invalid configuration: {:?}", e);
+                    None
+                }
+            }
+        }
+    }
+
+    fn from_hashmap(hashmap: &HashMap<String, String>) -> Result<Self, InvalidConfigurationError> {
+        let token_length = u32::from_str(
+            hashmap
+                .get("token_length")
+                .ok_or_else(|| InvalidConfigurationError::new("Missing key: `token_length`"))?,
+        )
+        .map_err(|_| InvalidConfigurationError::new("Invalid value for `token_length`"))?;
+        if token_length == 0 {
+            return Err(InvalidConfigurationError::new(
+                "Invalid value for `token_length`",
+            ));
+        }
+        // `normalize` is optional so that configurations written for CCFinderSW can be reused.
+        let normalize = match hashmap.get("normalize") {
+            Some(s) => bool::from_str(s)
+                .map_err(|_| InvalidConfigurationError::new("Invalid value for `normalize`"))?,
+            None => true,
+        };
+
+        Ok(NativeConfig {
+            token_length,
+            normalize,
+        })
+    }
+
+    pub fn get_token_length(&self) -> usize {
+        self.token_length as usize
+    }
+
+    pub fn is_normalization_enabled(&self) -> bool {
+        self.normalize
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use log::error;
+
+use crate::config::{CloneDetectorKind, Config};
+use crate::error::InvalidConfigurationError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Granularity {
+    Functions,
+    Blocks,
+}
+
+fn deserialize_granularity(s: &str) -> Result<Granularity, ()> {
+    match s {
+        "functions" => Ok(Granularity::Functions),
+        "blocks" => Ok(Granularity::Blocks),
+        _ => Err(()),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NiCadConfig {
+    executable_path: PathBuf,
+    granularity: Granularity,
+    language: String,
+    threshold: f64,
+    configuration: String,
+    staged_extension: String,
+}
+
+impl NiCadConfig {
+    pub fn try_from_config(config: &Config) -> Option<Self> {
+        if config.clone_detector_kind != CloneDetectorKind::NiCad {
+            None
+        } else {
+            match NiCadConfig::from_hashmap(&config.clone_detector_config) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    error!("Invalid configuration: {:?}", e);
+                    None
+                }
+            }
+        }
+    }
+
+    fn from_hashmap(hashmap: &HashMap<String, String>) -> Result<Self, InvalidConfigurationError> {
+        let executable_path =
+            PathBuf::from(
+                shellexpand::tilde(hashmap.get("executable_path").ok_or_else(|| {
+                    InvalidConfigurationError::new("Missing key: `executable_path`")
+                })?)
+                .as_ref(),
+            )
+            .canonicalize()
+            .map_err(|_| {
+                InvalidConfigurationError::new("Could not canonicalize the specified path.")
+            })?;
+        let granularity = deserialize_granularity(
+            hashmap
+                .get("granularity")
+                .ok_or_else(|| InvalidConfigurationError::new("Missing key: `granularity`"))?,
+        )
+        .map_err(|_| InvalidConfigurationError::new("Invalid value for `granularity`"))?;
+        let language = hashmap
+            .get("language")
+            .ok_or_else(|| InvalidConfigurationError::new("Missing key: `language`"))?
+            .clone();
+        let threshold = f64::from_str(
+            hashmap
+                .get("threshold")
+                .ok_or_else(|| InvalidConfigurationError::new("Missing key: `threshold`"))?,
+        )
+        .map_err(|_| InvalidConfigurationError::new("Invalid value for `threshold`"))?;
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(InvalidConfigurationError::new(
+                "Invalid value for `threshold`",
+            ));
+        }
+        let configuration = hashmap
+            .get("configuration")
+            .cloned()
+            .unwrap_or_else(|| String::from("default-report"));
+        let staged_extension = hashmap
+            .get("staged_extension")
+            .cloned()
+            .unwrap_or_else(|| String::from("c"));
+
+        Ok(NiCadConfig {
+            executable_path,
+            granularity,
+            language,
+            threshold,
+            configuration,
+            staged_extension,
+        })
+    }
+
+    pub fn get_executable_path_as_string(&self) -> String {
+        String::from(self.executable_path.to_str().unwrap())
+    }
+
+    pub fn granularity_to_option_value(&self) -> String {
+        match self.granularity {
+            Granularity::Functions => String::from("functions"),
+            Granularity::Blocks => String::from("blocks"),
+        }
+    }
+
+    pub fn language_to_option_value(&self) -> String {
+        self.language.clone()
+    }
+
+    pub fn configuration_to_option_value(&self) -> String {
+        self.configuration.clone()
+    }
+
+    /// Returns the largest difference of the clone pairs to keep, which must not exceed the
+    /// threshold in the NiCad configuration.
+    pub fn get_threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Returns the lowest similarity (in percent) of the clone pairs to keep.
+    pub fn get_minimum_similarity(&self) -> f64 {
+        (1.0 - self.threshold) * 100.0
+    }
+
+    /// NiCad selects the source files by the extension of the language, so the staged files
+    /// are given this extension in addition to their original one.
+    pub fn get_staged_file_name(&self, file_name: &str) -> String {
+        format!("{}.{}", file_name, self.staged_extension)
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use log::error;
+
+use crate::config::{CloneDetectorKind, Config};
+use crate::error::InvalidConfigurationError;
+
+/// The configuration of a detector running as a plugin process (see `runner::plugin`).
+#[derive(Clone, Debug)]
+pub struct PluginConfig {
+    command: Vec<String>,
+    sources_dir: String,
+    /// The entries passed to the plugin in the handshake.
+    settings: HashMap<String, String>,
+}
+
+impl PluginConfig {
+    pub fn try_from_config(config: &Config) -> Option<Self> {
+        if config.clone_detector_kind != CloneDetectorKind::Plugin {
+            None
+        } else {
+            match PluginConfig::from_hashmap(&config.clone_detector_config) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    error!("Invalid configuration: {:?}", e);
+                    None
+                }
+            }
+        }
+    }
+
+    fn from_hashmap(hashmap: &HashMap<String, String>) -> Result<Self, InvalidConfigurationError> {
+        let mut command: Vec<String> = hashmap
+            .get("command")
+            .ok_or_else(|| InvalidConfigurationError::new("Missing key: `command`"))?
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        if command.is_empty() {
+            return Err(InvalidConfigurationError::new(
+                "Invalid value for `command`",
+            ));
+        }
+        command[0] = String::from(shellexpand::tilde(&command[0]).as_ref());
+        let sources_dir = hashmap
+            .get("sources_dir")
+            .cloned()
+            .unwrap_or_else(|| String::from("src"));
+        let settings = hashmap
+            .iter()
+            .filter(|(k, _)| k.as_str() != "command" && k.as_str() != "sources_dir")
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+
+        Ok(PluginConfig {
+            command,
+            sources_dir,
+            settings,
+        })
+    }
+
+    /// Returns the program and the arguments to launch the plugin.
+    pub fn get_command(&self) -> &[String] {
+        &self.command
+    }
+
+    pub fn get_program(&self) -> String {
+        self.command[0].clone()
+    }
+
+    pub fn get_sources_dir(&self) -> &str {
+        self.sources_dir.as_str()
+    }
+
+    pub fn get_settings(&self) -> &HashMap<String, String> {
+        &self.settings
+    }
+}
@@ -1,5 +1,8 @@
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
+
+use crate::runner::process::CapturedOutput;
 
 #[derive(Debug)]
 pub struct NoValidConfigurationError;
@@ -57,11 +60,26 @@ impl Error for InvalidConfigurationError {}
 #[derive(Debug)]
 pub struct RunnerProcessFailedError {
     status_code: i32,
+    output: Option<CapturedOutput>,
 }
 
 impl RunnerProcessFailedError {
     pub fn new(status_code: i32) -> Self {
-        RunnerProcessFailedError { status_code }
+        RunnerProcessFailedError {
+            status_code,
+            output: None,
+        }
+    }
+
+    pub fn with_output(self, output: CapturedOutput) -> Self {
+        RunnerProcessFailedError {
+            output: Some(output),
+            ..self
+        }
+    }
+
+    pub fn get_output(&self) -> Option<&CapturedOutput> {
+        self.output.as_ref()
     }
 }
 
@@ -108,3 +126,200 @@ impl fmt::Display for FileNotFoundFromResultError {
 }
 
 impl Error for FileNotFoundFromResultError {}
+
+#[derive(Debug)]
+pub struct InvalidNiCadReport {
+    description: String,
+}
+
+impl InvalidNiCadReport {
+    pub fn new(description: &str) -> InvalidNiCadReport {
+        InvalidNiCadReport {
+            description: String::from(description),
+        }
+    }
+}
+
+impl fmt::Display for InvalidNiCadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The report is in invalid format: {}", self.description)
+    }
+}
+
+impl Error for InvalidNiCadReport {}
+
+#[derive(Debug)]
+pub struct InvalidDetectorOutput {
+    description: String,
+}
+
+impl InvalidDetectorOutput {
+    pub fn new(description: &str) -> InvalidDetectorOutput {
+        InvalidDetectorOutput {
+            description: String::from(description),
+        }
+    }
+}
+
+impl fmt::Display for InvalidDetectorOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The detector output is in invalid format: {}",
+            self.description
+        )
+    }
+}
+
+impl Error for InvalidDetectorOutput {}
+
+#[derive(Debug)]
+pub struct BatchFailedError {
+    description: String,
+    output: Option<CapturedOutput>,
+}
+
+impl BatchFailedError {
+    pub fn new(description: &str) -> BatchFailedError {
+        BatchFailedError {
+            description: String::from(description),
+            output: None,
+        }
+    }
+
+    /// Keeps the output of the detector which failed the batch for the logs of its jobs.
+    pub fn with_output(self, output: Option<CapturedOutput>) -> Self {
+        BatchFailedError { output, ..self }
+    }
+
+    pub fn get_output(&self) -> Option<&CapturedOutput> {
+        self.output.as_ref()
+    }
+}
+
+impl fmt::Display for BatchFailedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The batch containing the job failed: {}",
+            self.description
+        )
+    }
+}
+
+impl Error for BatchFailedError {}
+
+#[derive(Debug)]
+pub struct WorkerFailedError {
+    description: String,
+}
+
+impl WorkerFailedError {
+    pub fn new(description: &str) -> WorkerFailedError {
+        WorkerFailedError {
+            description: String::from(description),
+        }
+    }
+}
+
+impl fmt::Display for WorkerFailedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The detector worker failed: {}", self.description)
+    }
+}
+
+impl Error for WorkerFailedError {}
+
+#[derive(Clone, Debug)]
+pub struct JobTimedOutError {
+    timeout: Duration,
+    output: Option<CapturedOutput>,
+}
+
+impl JobTimedOutError {
+    pub fn new(timeout: Duration) -> JobTimedOutError {
+        JobTimedOutError {
+            timeout,
+            output: None,
+        }
+    }
+
+    /// Attaches the output of the killed detector.
+    pub fn with_output(self, output: CapturedOutput) -> Self {
+        JobTimedOutError {
+            output: Some(output),
+            ..self
+        }
+    }
+
+    pub fn get_output(&self) -> Option<&CapturedOutput> {
+        self.output.as_ref()
+    }
+}
+
+impl fmt::Display for JobTimedOutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The job timed out after {} second(s).",
+            self.timeout.as_secs()
+        )
+    }
+}
+
+impl Error for JobTimedOutError {}
+
+#[derive(Clone, Debug, Default)]
+pub struct JobCancelledError {
+    output: Option<CapturedOutput>,
+}
+
+impl JobCancelledError {
+    pub fn new() -> JobCancelledError {
+        JobCancelledError { output: None }
+    }
+
+    /// Attaches the output of the killed detector.
+    pub fn with_output(self, output: CapturedOutput) -> Self {
+        JobCancelledError {
+            output: Some(output),
+        }
+    }
+
+    pub fn get_output(&self) -> Option<&CapturedOutput> {
+        self.output.as_ref()
+    }
+}
+
+impl fmt::Display for JobCancelledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The job was cancelled.")
+    }
+}
+
+impl Error for JobCancelledError {}
+
+#[derive(Debug)]
+pub struct InvalidShardError {
+    shard: String,
+}
+
+impl InvalidShardError {
+    pub fn new(shard: &str) -> InvalidShardError {
+        InvalidShardError {
+            shard: String::from(shard),
+        }
+    }
+}
+
+impl fmt::Display for InvalidShardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid shard: `{}` (expected INDEX/COUNT with 1 <= INDEX <= COUNT)",
+            self.shard
+        )
+    }
+}
+
+impl Error for InvalidShardError {}
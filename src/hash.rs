@@ -0,0 +1,72 @@
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// 128-bit FNV-1a hasher.
+///
+/// Unlike `std::collections::hash_map::DefaultHasher`, the digest doesn't change between the
+/// builds, so it can be stored on disk (e.g. as a job id).
+pub struct StableHasher {
+    state: u128,
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher::new()
+    }
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher {
+            state: FNV_OFFSET_BASIS,
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.state ^= *b as u128;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Hashes the bytes with their length so that the boundaries of the fields are kept.
+    pub fn update_bytes(&mut self, bytes: &[u8]) {
+        self.update(&(bytes.len() as u64).to_le_bytes());
+        self.update(bytes);
+    }
+
+    pub fn update_str(&mut self, s: &str) {
+        self.update_bytes(s.as_bytes());
+    }
+
+    pub fn finish(&self) -> u128 {
+        self.state
+    }
+
+    pub fn finish_hex(&self) -> String {
+        format!("{:032x}", self.state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hash::StableHasher;
+
+    #[test]
+    fn test_stable_hasher() {
+        let hasher = StableHasher::new();
+        assert_eq!(hasher.finish_hex(), "6c62272e07bb014262b821756295c58d");
+
+        let mut hasher = StableHasher::new();
+        hasher.update(b"a");
+        assert_eq!(hasher.finish_hex(), "d228cb696f1a8caf78912b704e4a8964");
+
+        let mut ab = StableHasher::new();
+        ab.update_str("a");
+        ab.update_str("b");
+        let mut a_b = StableHasher::new();
+        a_b.update_str("ab");
+        a_b.update_str("");
+        assert_ne!(ab.finish_hex(), a_b.finish_hex());
+    }
+}
@@ -1,12 +1,23 @@
 use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 use semver::Version;
 
+use zip::result::ZipError;
+
 use serde_derive::{Deserialize, Serialize};
 
 use crate::clone_pair::ClonePair;
-use crate::error::InvalidPathError;
+use crate::error::{
+    BatchFailedError, FileNotFoundFromResultError, InvalidCCFinderSWResult,
+    InvalidConfigurationError, InvalidDetectorOutput, InvalidNiCadReport, InvalidPathError,
+    JobCancelledError, JobTimedOutError, RunnerProcessFailedError, WorkerFailedError,
+};
+use crate::hash::StableHasher;
+use crate::runner::process::CapturedOutput;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SourceInfo {
@@ -14,6 +25,16 @@ pub struct SourceInfo {
 }
 
 impl SourceInfo {
+    pub fn new(location: &str) -> Self {
+        SourceInfo {
+            location: String::from(location),
+        }
+    }
+
+    pub fn get_location(&self) -> &str {
+        self.location.as_str()
+    }
+
     pub fn get_location_from(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>> {
         Ok(PathBuf::from(path)
             .join(Path::new(&self.location))
@@ -40,10 +61,36 @@ pub struct LibraryInfo {
     name: String,
     version: Version,
     location: String,
-    pub(crate) archive_root: String,
+    archive_root: String,
 }
 
 impl LibraryInfo {
+    pub fn new(name: &str, version: Version, location: &str, archive_root: &str) -> Self {
+        LibraryInfo {
+            name: String::from(name),
+            version,
+            location: String::from(location),
+            archive_root: String::from(archive_root),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn get_version(&self) -> &Version {
+        &self.version
+    }
+
+    pub fn get_location(&self) -> &str {
+        self.location.as_str()
+    }
+
+    /// Returns the directory in the archive which contains the library.
+    pub fn get_archive_root(&self) -> &str {
+        self.archive_root.as_str()
+    }
+
     pub fn get_absolute_location(&self, database_path: &Path) -> Result<PathBuf, Box<dyn Error>> {
         Ok(PathBuf::from(database_path)
             .join(Path::new("libraries"))
@@ -54,34 +101,363 @@ impl LibraryInfo {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Job {
-    pub(crate) project: SourceInfo,
-    pub(crate) example_sketch: SourceInfo,
-    pub(crate) library_info: LibraryInfo,
+    project: SourceInfo,
+    example_sketch: SourceInfo,
+    library_info: LibraryInfo,
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} {})",
+            self.example_sketch.get_location(),
+            self.library_info.get_name(),
+            self.library_info.get_version()
+        )
+    }
 }
 
 impl Job {
+    pub fn new(project: SourceInfo, example_sketch: SourceInfo, library_info: LibraryInfo) -> Self {
+        Job {
+            project,
+            example_sketch,
+            library_info,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(toml::from_str(contents.as_str())?)
+    }
+
+    pub fn get_project(&self) -> &SourceInfo {
+        &self.project
+    }
+
+    pub fn get_example_sketch(&self) -> &SourceInfo {
+        &self.example_sketch
+    }
+
+    pub fn get_library_info(&self) -> &LibraryInfo {
+        &self.library_info
+    }
+
+    /// Returns the key which orders the jobs by project, library, version and example sketch.
+    pub fn sort_key(&self) -> (&str, &str, &Version, &str) {
+        (
+            &self.project.location,
+            &self.library_info.name,
+            &self.library_info.version,
+            &self.example_sketch.location,
+        )
+    }
+
+    /// Returns the id which identifies the job across the runs.
+    pub fn get_id(&self) -> String {
+        let mut hasher = StableHasher::new();
+        hasher.update_str(&self.project.location);
+        hasher.update_str(&self.example_sketch.location);
+        hasher.update_str(&self.library_info.name);
+        hasher.update_str(&self.library_info.version.to_string());
+        hasher.update_str(&self.library_info.location);
+        hasher.update_str(&self.library_info.archive_root);
+        hasher.finish_hex()
+    }
+
     pub fn create_result(&self, pairs: Vec<ClonePair>) -> JobResult {
-        if pairs.is_empty() {
-            JobResult {
-                job: self.clone(),
-                clone_pairs: None,
-            }
+        JobResult {
+            status: JobStatus::Ok,
+            workdirs: Vec::new(),
+            job: self.clone(),
+            error: None,
+            clone_pairs: if pairs.is_empty() { None } else { Some(pairs) },
+        }
+    }
+
+    /// Creates the result of the job which failed or timed out with the error.
+    pub fn create_error_result(&self, e: &(dyn Error + 'static)) -> JobResult {
+        self.create_failed_result(JobError::from_error(e))
+    }
+
+    pub fn create_failed_result(&self, error: JobError) -> JobResult {
+        JobResult {
+            status: match error.kind {
+                JobErrorKind::Timeout => JobStatus::TimedOut,
+                JobErrorKind::Cancelled => JobStatus::Cancelled,
+                _ => JobStatus::Failed,
+            },
+            workdirs: Vec::new(),
+            job: self.clone(),
+            error: Some(error),
+            clone_pairs: None,
+        }
+    }
+
+    /// Creates the result of the job which was not run because the run was cancelled.
+    pub fn create_cancelled_result(&self) -> JobResult {
+        JobResult {
+            status: JobStatus::Cancelled,
+            workdirs: Vec::new(),
+            job: self.clone(),
+            error: None,
+            clone_pairs: None,
+        }
+    }
+
+    /// Creates the result of the job which was not run.
+    pub fn create_skipped_result(&self) -> JobResult {
+        JobResult {
+            status: JobStatus::Skipped,
+            workdirs: Vec::new(),
+            job: self.clone(),
+            error: None,
+            clone_pairs: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Ok,
+    Failed,
+    TimedOut,
+    /// The run was cancelled before or while running the job.
+    Cancelled,
+    /// The job was not run (e.g. the thread running it died).
+    Skipped,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobErrorKind {
+    /// Reading or staging the sources failed.
+    Io,
+    /// The library archive is broken or doesn't contain the example sketch.
+    Archive,
+    Configuration,
+    /// The detector exited abnormally.
+    Process,
+    /// The output of the detector could not be read.
+    InvalidOutput,
+    Batch,
+    Worker,
+    Timeout,
+    Cancelled,
+    Panic,
+    Other,
+}
+
+/// The reason why the job failed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobError {
+    kind: JobErrorKind,
+    message: String,
+    /// The output of the detector which exited abnormally.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output: Option<CapturedOutput>,
+}
+
+impl JobError {
+    pub fn new(kind: JobErrorKind, message: &str) -> Self {
+        JobError {
+            kind,
+            message: String::from(message),
+            output: None,
+        }
+    }
+
+    pub fn get_kind(&self) -> JobErrorKind {
+        self.kind
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn get_output(&self) -> Option<&CapturedOutput> {
+        self.output.as_ref()
+    }
+
+    pub fn from_error(e: &(dyn Error + 'static)) -> Self {
+        let kind = if e.is::<std::io::Error>() {
+            JobErrorKind::Io
+        } else if e.is::<ZipError>() {
+            JobErrorKind::Archive
+        } else if e.is::<InvalidConfigurationError>() || e.is::<InvalidPathError>() {
+            JobErrorKind::Configuration
+        } else if e.is::<RunnerProcessFailedError>() {
+            JobErrorKind::Process
+        } else if e.is::<InvalidCCFinderSWResult>()
+            || e.is::<FileNotFoundFromResultError>()
+            || e.is::<InvalidNiCadReport>()
+            || e.is::<InvalidDetectorOutput>()
+            || e.is::<serde_json::Error>()
+            || e.is::<nom::Err<()>>()
+        {
+            JobErrorKind::InvalidOutput
+        } else if e.is::<BatchFailedError>() {
+            JobErrorKind::Batch
+        } else if e.is::<WorkerFailedError>() {
+            JobErrorKind::Worker
+        } else if e.is::<JobTimedOutError>() {
+            JobErrorKind::Timeout
+        } else if e.is::<JobCancelledError>() {
+            JobErrorKind::Cancelled
         } else {
-            JobResult {
-                job: self.clone(),
-                clone_pairs: Some(pairs),
-            }
+            JobErrorKind::Other
+        };
+        JobError {
+            output: e
+                .downcast_ref::<RunnerProcessFailedError>()
+                .and_then(|e| e.get_output())
+                .or_else(|| {
+                    e.downcast_ref::<BatchFailedError>()
+                        .and_then(|e| e.get_output())
+                })
+                .or_else(|| {
+                    e.downcast_ref::<JobTimedOutError>()
+                        .and_then(|e| e.get_output())
+                })
+                .or_else(|| {
+                    e.downcast_ref::<JobCancelledError>()
+                        .and_then(|e| e.get_output())
+                })
+                .cloned(),
+            ..JobError::new(kind, e.to_string().as_str())
         }
     }
 }
 
-#[derive(Clone, Serialize)]
+// NOTE: The status must precede the tables for TOML.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct JobResult {
+    status: JobStatus,
+    /// The working directories kept for debugging (see `--keep-workdirs`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    workdirs: Vec<String>,
     job: Job,
+    error: Option<JobError>,
     clone_pairs: Option<Vec<ClonePair>>,
 }
 
-#[derive(Serialize)]
-pub struct JobResults {
-    pub(crate) results: Vec<JobResult>,
+impl JobResult {
+    pub fn get_status(&self) -> JobStatus {
+        self.status
+    }
+
+    pub fn get_job(&self) -> &Job {
+        &self.job
+    }
+
+    pub fn get_error(&self) -> Option<&JobError> {
+        self.error.as_ref()
+    }
+
+    pub fn get_clone_pairs(&self) -> &[ClonePair] {
+        self.clone_pairs.as_deref().unwrap_or(&[])
+    }
+
+    pub fn sort_clone_pairs(&mut self) {
+        if let Some(pairs) = &mut self.clone_pairs {
+            pairs.sort_by(|a, b| a.cmp_position(b));
+        }
+    }
+
+    pub fn with_workdirs(self, workdirs: Vec<PathBuf>) -> Self {
+        JobResult {
+            workdirs: workdirs
+                .iter()
+                .map(|p| String::from(p.to_str().unwrap()))
+                .collect(),
+            ..self
+        }
+    }
+}
+
+/// Returns the job of `MyProject.ino` and the example sketch of `Library` 1.0.0 for the tests.
+#[cfg(any(test, feature = "test-util"))]
+pub fn test_job(example: &str) -> Job {
+    Job::new(
+        SourceInfo::new("MyProject.ino"),
+        SourceInfo::new(example),
+        LibraryInfo::new(
+            "Library",
+            Version::new(1, 0, 0),
+            "Library/1.0.0/Library-1.0.0.zip",
+            "Library-1.0.0",
+        ),
+    )
+}
+
+#[cfg(test)]
+impl Job {
+    pub(crate) fn with_project(self, location: &str) -> Self {
+        Job {
+            project: SourceInfo::new(location),
+            ..self
+        }
+    }
+
+    /// Replaces the library with the version 1.0.0 of another one.
+    pub(crate) fn with_library(self, name: &str) -> Self {
+        Job {
+            library_info: LibraryInfo::new(
+                name,
+                Version::new(1, 0, 0),
+                &format!("{0}/1.0.0/{0}-1.0.0.zip", name),
+                &format!("{}-1.0.0", name),
+            ),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_archive_root(self, archive_root: &str) -> Self {
+        Job {
+            library_info: LibraryInfo {
+                archive_root: String::from(archive_root),
+                ..self.library_info
+            },
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::time::Duration;
+
+    use crate::error::{BatchFailedError, JobTimedOutError, RunnerProcessFailedError};
+    use crate::job::{test_job, JobErrorKind, JobStatus};
+    use crate::runner::process::CapturedOutput;
+
+    #[test]
+    fn test_create_error_result() {
+        let job = test_job("Example/Example.ino");
+        let res = job.create_error_result(&JobTimedOutError::new(Duration::from_secs(10)));
+        assert_eq!(res.get_status(), JobStatus::TimedOut);
+        let res = job.create_error_result(&RunnerProcessFailedError::new(1));
+        assert_eq!(res.get_status(), JobStatus::Failed);
+        assert_eq!(res.error.unwrap().kind, JobErrorKind::Process);
+        let output = CapturedOutput {
+            stderr: String::from("Exception in thread \"main\""),
+            ..CapturedOutput::default()
+        };
+        let res =
+            job.create_error_result(&RunnerProcessFailedError::new(1).with_output(output.clone()));
+        assert_eq!(res.error.unwrap().output, Some(output.clone()));
+        let res = job.create_error_result(
+            &BatchFailedError::new("Process exited abnormally").with_output(Some(output.clone())),
+        );
+        let error = res.error.unwrap();
+        assert_eq!(error.kind, JobErrorKind::Batch);
+        assert_eq!(error.output, Some(output));
+        let res = job.create_error_result(&io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(res.error.unwrap().kind, JobErrorKind::Io);
+    }
 }
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::{info, warn};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::job::JobResult;
+
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    id: String,
+    result: JobResult,
+}
+
+/// An append-only record of the finished jobs, one JSON object per line.
+///
+/// The journal is written as soon as each job finishes, so that an interrupted run can be
+/// resumed without running the finished jobs again. The jobs which failed or timed out are not
+/// recorded and will be run again.
+pub struct Journal {
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Returns the path of the journal for the output file (e.g. `result.toml.journal`).
+    pub fn get_path(output_path: &Path) -> PathBuf {
+        let mut path = output_path.as_os_str().to_os_string();
+        path.push(".journal");
+        PathBuf::from(path)
+    }
+
+    /// Reads the results recorded in the journal keyed by the job ids.
+    ///
+    /// A missing journal is treated as empty. The lines which can't be read (e.g. the last line
+    /// written by a crashed run) are skipped.
+    pub fn read_results(path: &Path) -> Result<HashMap<String, JobResult>, Box<dyn Error>> {
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("No journal found: {}", path.to_str().unwrap());
+                return Ok(HashMap::new());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut results = HashMap::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<JournalEntry>(&line) {
+                Ok(entry) => {
+                    results.insert(entry.id, entry.result);
+                }
+                Err(e) => warn!("Skipping a broken journal entry at line {}: {}", i + 1, e),
+            }
+        }
+        Ok(results)
+    }
+
+    /// Opens the journal. The existing entries are kept only when `append` is set.
+    pub fn open(path: &Path, append: bool) -> Result<Self, Box<dyn Error>> {
+        let file = if append {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(path)?;
+            // Terminate the line cut off by a crash so that it doesn't swallow the next entry.
+            if file.metadata()?.len() > 0 {
+                let mut last = [0u8];
+                file.seek(SeekFrom::End(-1))?;
+                file.read_exact(&mut last)?;
+                if last[0] != b'\n' {
+                    file.write_all(b"\n")?;
+                }
+            }
+            file
+        } else {
+            File::create(path)?
+        };
+        Ok(Journal {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, result: &JobResult) -> Result<(), Box<dyn Error>> {
+        let entry = JournalEntry {
+            id: result.get_job().get_id(),
+            result: result.clone(),
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        // NOTE: Writing the whole line at once keeps the entries from the threads apart.
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use crate::job::test_job;
+    use crate::journal::Journal;
+
+    #[test]
+    fn test_resume_from_journal() {
+        let job = test_job("Example/Example.ino");
+        let dir = tempfile::tempdir().unwrap();
+        let path = Journal::get_path(&dir.path().join("result.toml"));
+        assert!(Journal::read_results(&path).unwrap().is_empty());
+
+        let journal = Journal::open(&path, false).unwrap();
+        journal.record(&job.create_result(Vec::new())).unwrap();
+        drop(journal);
+        // A line cut off by a crash must not prevent resuming.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"id\": \"").unwrap();
+
+        let results = Journal::read_results(&path).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results.contains_key(&job.get_id()));
+
+        let journal = Journal::open(&path, true).unwrap();
+        journal.record(&job.create_result(Vec::new())).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+        assert!(contents.lines().last().unwrap().starts_with("{\"id\""));
+    }
+}
@@ -0,0 +1,53 @@
+//! Hugin runs a clone detector on the jobs of a session generated by Munin.
+//!
+//! The `hugin` binary is a thin command line interface on top of this crate. A program which
+//! embeds Hugin loads a [`Config`] and a [`Session`], creates a [`Runner`] with
+//! [`create_runner`] and runs the jobs with [`run_jobs`], which reports the results through a
+//! [`Progress`].
+//!
+//! The modules are private: everything a program needs is re-exported here.
+
+pub(crate) mod cache;
+pub(crate) mod cancel;
+pub(crate) mod clone_pair;
+pub(crate) mod config;
+pub(crate) mod error;
+pub(crate) mod hash;
+pub(crate) mod job;
+pub(crate) mod journal;
+pub(crate) mod output;
+pub(crate) mod plan;
+pub(crate) mod runner;
+pub(crate) mod scheduler;
+pub(crate) mod session;
+pub(crate) mod shard;
+pub(crate) mod validate;
+
+pub use crate::clone_pair::{ClonePair, CodePosition, CodeSlice, Scores};
+pub use crate::config::{BatchKey, CloneDetectorKind, Config, JobTimeouts};
+pub use crate::job::{Job, JobError, JobErrorKind, JobResult, JobStatus, LibraryInfo, SourceInfo};
+pub use crate::runner::{create_runner, Runner};
+pub use crate::scheduler::{describe_batch, run_jobs, JobPool, PoolRun, Progress};
+pub use crate::session::Session;
+
+// Running the jobs: the signals cancel the runs, and the work directories and archives are
+// shared by the runners of a session.
+pub use crate::cancel::{get_signal, install_signal_handlers, is_cancelled};
+pub use crate::runner::archive::ArchiveCache;
+pub use crate::runner::batch::group_jobs;
+pub use crate::runner::caching::CachingRunner;
+pub use crate::runner::process::CapturedOutput;
+pub use crate::runner::workdir::{KeepWorkDirs, WorkDirs};
+pub use crate::shard::Shard;
+
+// Reading and writing the results.
+pub use crate::cache::{CacheStats, ResultCache};
+pub use crate::error::{InvalidConfigurationError, InvalidShardError};
+pub use crate::journal::Journal;
+pub use crate::output::{read_results, ResultFormat, ResultWriter, Summary};
+pub use crate::plan::Plan;
+pub use crate::validate::{validate_session, ValidationReport};
+
+// Fixtures for the tests.
+#[cfg(any(test, feature = "test-util"))]
+pub use crate::job::test_job;
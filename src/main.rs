@@ -1,95 +1,15 @@
 use std::error::Error;
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::str::FromStr;
-use std::sync::Arc;
-use std::thread;
-use std::thread::JoinHandle;
+use std::path::Path;
 
 use clap::clap_app;
 
 use flexi_logger::{Duplicate, LevelFilter, LogSpecBuilder, LogSpecification, Logger};
 
-use log::{debug, error, info, warn};
+use log::info;
 
-use num_integer::div_ceil;
+mod command;
 
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-
-mod clone_pair;
-mod config;
-mod error;
-mod job;
-mod runner;
-mod session;
-
-use crate::config::ccfindersw::CCFinderSWConfig;
-use crate::config::Config;
-use crate::error::NoValidConfigurationError;
-use crate::job::{Job, JobResult, JobResults};
-use crate::runner::ccfindersw::CCFinderSWRunner;
-use crate::runner::Runner;
-use crate::session::Session;
-
-fn run_jobs<R>(jobs: Arc<Vec<Job>>, runner: Arc<R>, number_of_threads: usize) -> Vec<JobResult>
-where
-    R: Runner + Sync + Send + 'static,
-{
-    let mut results = Vec::new();
-    let divided_jobs = jobs.chunks(div_ceil(jobs.len(), number_of_threads));
-    if divided_jobs.len() != number_of_threads {
-        error!("BUG: Could not divide the job to the required number of parts.");
-    }
-    info!("Running detector on {} thread(s)...", divided_jobs.len());
-    let m = MultiProgress::new();
-    let style = ProgressStyle::default_bar()
-        .template("PROGRESS: {wide_bar} {pos}/{len}")
-        .progress_chars("##-");
-    let threads = divided_jobs
-        .map(|thread_jobs| {
-            let bar = m.add(ProgressBar::new(thread_jobs.len() as u64));
-            bar.set_style(style.clone());
-            let runner = runner.clone();
-            let mut temp = Vec::new();
-            temp.extend_from_slice(thread_jobs);
-            let thread_jobs = temp;
-            thread::spawn(move || {
-                let mut thread_results = Vec::new();
-                for j in thread_jobs {
-                    bar.inc(1);
-                    match runner.run_job(j.clone()) {
-                        Ok(res) => {
-                            let job_result = j.create_result(res);
-                            thread_results.push(job_result);
-                        }
-                        Err(e) => {
-                            error!("Job failed with error: {:?}", e);
-                        }
-                    }
-                }
-                bar.finish();
-                thread_results
-            })
-        })
-        .collect::<Vec<JoinHandle<Vec<JobResult>>>>();
-
-    m.join().unwrap();
-    for t in threads {
-        match t.join() {
-            Ok(res) => {
-                let mut temp = Vec::new();
-                temp.extend(res);
-                results.append(&mut temp);
-            }
-            Err(e) => {
-                error!("A thread failed with error: {:?}", e);
-            }
-        }
-    }
-
-    results
-}
+use hugin::Config;
 
 fn main() -> Result<(), Box<dyn Error>> {
     // Parse options
@@ -97,12 +17,65 @@ fn main() -> Result<(), Box<dyn Error>> {
         (version: "0.1.0")
         (author: "ikubaku <hide4d51@gmail.com")
         (about: "An Arduino Project code cloning detector: Job dispatcher module")
-        (@arg CONFIG: -c --config +takes_value "configuration filename")
-        (@arg LOG: -l --log "enable logging to file")
-        (@arg verbose: -v --verbose ... "verbosity of the logging (max stack: 2)")
-        (@arg no_warning: -q --no_warn "suppress warning message (note that verbosity option overrides this)")
-        (@arg SESSION: +required "the Hugin session generated by Munin")
-        (@arg OUTPUT: +required "the output file name for the result")
+        (@setting SubcommandRequiredElseHelp)
+        (@arg CONFIG: -c --config +takes_value +global "configuration filename")
+        (@arg LOG: -l --log +global "enable logging to file")
+        (@arg verbose: -v --verbose ... +global "verbosity of the logging (max stack: 2)")
+        (@arg no_warning: -q --no_warn +global "suppress warning message (note that verbosity option overrides this)")
+        (@subcommand run =>
+            (about: "run the clone detector on the jobs of a session")
+            (@arg FORMAT: -f --format +takes_value possible_value[toml jsonl] "the format of the output (default: toml)")
+            (@arg RESUME: --resume "skip the jobs recorded in the journal of the previous run")
+            (@arg DRY_RUN: --("dry-run") "print the planned jobs and the detector command lines without running the detector")
+            (@arg PLAN: --plan +takes_value requires[DRY_RUN] "also write the plan of `--dry-run` to the file as JSON")
+            (@arg SHARD: --shard +takes_value "run only the jobs in the shard INDEX/COUNT (e.g. 2/4), chosen by a stable hash of each job")
+            (@arg KEEP_WORKDIRS: --("keep-workdirs") +takes_value possible_value[failed all] "keep the working directories of the failed (or all) jobs in `scratch_path`")
+            (@arg SESSION: +required "the Hugin session generated by Munin")
+            (@arg OUTPUT: +required "the output file name for the result"))
+        (@subcommand validate =>
+            (about: "check the session, the library archives and the detector without running any job")
+            (@arg SESSION: +required "the Hugin session generated by Munin"))
+        (@subcommand report =>
+            (about: "show the statistics of a result file")
+            (@arg TOP: --top +takes_value "the number of the libraries to show (default: 20)")
+            (@arg RESULTS: +required "the result file written by `run`"))
+        (@subcommand merge =>
+            (about: "merge the result files of the runs on the same session")
+            (@arg FORMAT: -f --format +takes_value possible_value[toml jsonl] "the format of the output (default: toml)")
+            (@arg OUTPUT: -o --output +takes_value +required "the output file name for the merged result")
+            (@arg SESSION: --session +takes_value "check that the inputs (e.g. the outputs of `run --shard`) have every job of the session exactly once")
+            (@arg INPUTS: +required ... "the result files to merge (the later ones win)"))
+        (@subcommand diff =>
+            (about: "show the jobs whose results differ between two result files")
+            (@arg OLD: +required "the old result file")
+            (@arg NEW: +required "the new result file"))
+        (@subcommand cache =>
+            (about: "manage the result cache (see `cache_path` in the configuration)")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand stats => (about: "show the number of entries and their size"))
+            (@subcommand prune =>
+                (about: "remove the entries which have not been used recently")
+                (@arg DAYS: +required "remove the entries not used for DAYS days"))
+            (@subcommand clear => (about: "remove all entries")))
+        (@subcommand serve =>
+            (about: "serve a local HTTP API to submit the sessions and fetch their results")
+            (@arg LISTEN: --listen +takes_value "the address to listen on (default: 127.0.0.1:8340)")
+            (@arg RESULTS_DIR: --("results-dir") +takes_value "the directory to write the results of the sessions to (default: hugin-results)"))
+        (@subcommand coordinate =>
+            (about: "lease the jobs of a session to the workers connecting over TCP and write their results")
+            (@arg FORMAT: -f --format +takes_value possible_value[toml jsonl] "the format of the output (default: toml)")
+            (@arg LISTEN: --listen +takes_value "the address to listen on, e.g. 0.0.0.0:8341 to accept remote workers (default: 127.0.0.1:8341)")
+            (@arg TOKEN: --token +takes_value "the token the workers must present, also read from HUGIN_CLUSTER_TOKEN (default: a random one, printed at start)")
+            (@arg LEASE: --lease +takes_value "the seconds after which the batches of a silent worker are dispatched again (default: 60)")
+            (@arg SESSION: +required "the Hugin session generated by Munin")
+            (@arg OUTPUT: +required "the output file name for the result"))
+        (@subcommand work =>
+            (about: "run the jobs leased by a coordinator with the detector of the configuration")
+            (@arg NAME: --name +takes_value "the name of the worker shown by the coordinator (default: <host>:<pid>)")
+            (@arg TOKEN: --token +takes_value "the token of the coordinator, also read from HUGIN_CLUSTER_TOKEN")
+            (@arg COORDINATOR: +required "the address of the coordinator, e.g. 192.168.0.2:8341"))
+        (@subcommand config =>
+            (about: "show the effective configuration including the defaults"))
     ).get_matches();
 
     // Initialize logger
@@ -144,121 +117,25 @@ fn main() -> Result<(), Box<dyn Error>> {
     info!("Started the logger.");
 
     // Load configuration
-    let config: Option<Config>;
-    if let Some(filename) = matches.value_of("CONFIG") {
+    let config = if let Some(filename) = matches.value_of("CONFIG") {
         info!("Loading configuration from file: {}...", filename);
-        let mut file = File::open(filename)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        config = Some(toml::from_str(contents.as_str())?);
-        println!(
-            "Munin database directory: {}",
-            config
-                .clone()
-                .unwrap()
-                .get_absolute_database_root_path()?
-                .to_str()
-                .unwrap(),
-        );
+        Config::load(Path::new(filename))?
     } else {
         info!("Using the default configuration.");
-        config = None;
-    }
-
-    // Secure the output path
-    let output_filename = PathBuf::from_str(matches.value_of("OUTPUT").unwrap())?;
-    let mut output_file = File::create(output_filename)?;
-
-    // Load session
-    let session_path = PathBuf::from_str(matches.value_of("SESSION").unwrap())?;
-    let session: Session;
-    {
-        info!("Loading session...");
-        let mut filename = session_path.clone();
-        filename.push(matches.value_of("SESSION").unwrap());
-        filename.push("session.toml");
-        let mut file = File::open(filename)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        session = toml::from_str(contents.as_str())?;
-        info!(
-            "The project path is: {}",
-            session
-                .get_absolute_project_path(&session_path)?
-                .to_str()
-                .unwrap(),
-        );
-        info!(
-            "The jobs path is: {}",
-            session
-                .get_absolute_jobs_path(&session_path)?
-                .to_str()
-                .unwrap()
-        );
-    }
-
-    // Load jobs
-    let mut jobs = Vec::new();
-    for job_file in session.get_absolute_jobs_path(&session_path)?.read_dir()? {
-        debug!("job_file: {:?}", job_file);
-        let path = session
-            .get_absolute_jobs_path(&session_path)?
-            .join(job_file?.path());
-        let mut file = File::open(path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        let job: Job = toml::from_str(contents.as_str())?;
-        jobs.push(job);
-    }
-
-    match config {
-        None => {
-            let config = Config::default();
-            let number_of_jobs = config.number_of_jobs;
-            let ccfindersw_config = CCFinderSWConfig::try_from_config(&config).unwrap();
-
-            let project_path = session.get_absolute_project_path(&session_path)?;
-            let runner = CCFinderSWRunner::create(
-                ccfindersw_config,
-                &project_path,
-                &config.get_absolute_database_root_path()?,
-            );
-
-            let results = run_jobs(Arc::new(jobs), Arc::new(runner), number_of_jobs);
-
-            let results = JobResults { results };
-
-            let mut content = String::new();
-            content = toml::to_string(&results)?;
-            write!(output_file, "{}", content)?;
-        }
-        Some(config) => {
-            let number_of_jobs = config.number_of_jobs;
-            let ccfindersw_config =
-                CCFinderSWConfig::try_from_config(&config).ok_or_else(|| {
-                    error!("No valid configuration.");
-                    NoValidConfigurationError
-                })?;
-            println!("CCFinderSW configuration: {:?}", ccfindersw_config);
-
-            let project_path = session.get_absolute_project_path(&session_path)?;
-            let runner = CCFinderSWRunner::create(
-                ccfindersw_config,
-                &project_path,
-                &config.get_absolute_database_root_path()?,
-            );
-
-            let results = run_jobs(Arc::new(jobs), Arc::new(runner), number_of_jobs);
-
-            let results = JobResults { results };
+        Config::default()
+    };
 
-            let mut content = String::new();
-            content = toml::to_string(&results)?;
-            write!(output_file, "{}", content)?;
-        }
+    match matches.subcommand() {
+        ("run", Some(m)) => command::run::execute(&config, m),
+        ("validate", Some(m)) => command::validate::execute(&config, m),
+        ("report", Some(m)) => command::report::execute(m),
+        ("merge", Some(m)) => command::merge::execute(m),
+        ("diff", Some(m)) => command::diff::execute(m),
+        ("cache", Some(m)) => command::cache::execute(&config, m),
+        ("serve", Some(m)) => command::serve::execute(&config, m),
+        ("coordinate", Some(m)) => command::cluster::coordinator::execute(&config, m),
+        ("work", Some(m)) => command::cluster::worker::execute(&config, m),
+        ("config", Some(m)) => command::config::execute(&config, m),
+        _ => unreachable!(),
     }
-
-    info!("Exiting...");
-
-    Ok(())
 }
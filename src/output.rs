@@ -0,0 +1,351 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde_derive::{Deserialize, Serialize};
+
+use tempfile::NamedTempFile;
+
+use crate::job::{JobResult, JobStatus};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResultFormat {
+    /// One `[[results]]` table per job and a `[summary]` table at the end, which together form
+    /// the same document as the whole results serialized at once.
+    Toml,
+    /// One JSON object per job and a `{"summary": ...}` object at the end.
+    JsonLines,
+}
+
+impl ResultFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "toml" => Some(ResultFormat::Toml),
+            "jsonl" => Some(ResultFormat::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+/// The number of the jobs by their status.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub total: usize,
+    pub ok: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+    pub cancelled: usize,
+    pub skipped: usize,
+}
+
+impl Summary {
+    pub fn of(results: &[JobResult]) -> Self {
+        let mut summary = Summary::default();
+        for r in results {
+            summary.add(r.get_status());
+        }
+        summary
+    }
+
+    pub fn add(&mut self, status: JobStatus) {
+        self.total += 1;
+        match status {
+            JobStatus::Ok => self.ok += 1,
+            JobStatus::Failed => self.failed += 1,
+            JobStatus::TimedOut => self.timed_out += 1,
+            JobStatus::Cancelled => self.cancelled += 1,
+            JobStatus::Skipped => self.skipped += 1,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    results: &'a [JobResult],
+}
+
+#[derive(Serialize)]
+struct Footer<'a> {
+    summary: &'a Summary,
+}
+
+#[derive(Deserialize)]
+struct Document {
+    #[serde(default)]
+    results: Vec<JobResult>,
+}
+
+/// Reads the results written by `ResultWriter` in either format.
+pub fn read_results(path: &Path) -> Result<Vec<JobResult>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    if contents.trim_start().starts_with('{') {
+        let mut results = Vec::new();
+        for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+            let value: serde_json::Value = serde_json::from_str(line)?;
+            if value.get("summary").is_none() {
+                results.push(serde_json::from_value(value)?);
+            }
+        }
+        Ok(results)
+    } else {
+        let document: Document = toml::from_str(&contents)?;
+        Ok(document.results)
+    }
+}
+
+/// Sorts the results by job and the clone pairs of each result by position.
+fn sort_results(results: &mut [JobResult]) {
+    results.sort_by(|a, b| a.get_job().sort_key().cmp(&b.get_job().sort_key()));
+    for r in results {
+        r.sort_clone_pairs();
+    }
+}
+
+struct State {
+    file: File,
+    summary: Summary,
+}
+
+/// Writes each result to the output file as soon as its job finishes, and rewrites the output in
+/// a deterministic order at the end.
+pub struct ResultWriter {
+    path: PathBuf,
+    format: ResultFormat,
+    job_log_path: Option<PathBuf>,
+    state: Mutex<State>,
+}
+
+impl ResultWriter {
+    pub fn create(path: &Path, format: ResultFormat) -> Result<Self, Box<dyn Error>> {
+        Ok(ResultWriter {
+            path: PathBuf::from(path),
+            format,
+            job_log_path: None,
+            state: Mutex::new(State {
+                file: File::create(path)?,
+                summary: Summary::default(),
+            }),
+        })
+    }
+
+    /// Also writes the output of the detector to `<job_log_path>/<job id>.log` when a job fails.
+    pub fn with_job_log_path(self, job_log_path: &Path) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(job_log_path)?;
+        Ok(ResultWriter {
+            job_log_path: Some(PathBuf::from(job_log_path)),
+            ..self
+        })
+    }
+
+    fn write_job_log(&self, result: &JobResult) -> Result<(), Box<dyn Error>> {
+        let (job_log_path, error) = match (&self.job_log_path, result.get_error()) {
+            (Some(p), Some(e)) => (p, e),
+            _ => return Ok(()),
+        };
+        let output = match error.get_output() {
+            Some(o) => o,
+            None => return Ok(()),
+        };
+        let job = result.get_job();
+        let mut file = File::create(job_log_path.join(format!("{}.log", job.get_id())))?;
+        writeln!(file, "project: {}", job.get_project().get_location())?;
+        writeln!(
+            file,
+            "example sketch: {}",
+            job.get_example_sketch().get_location()
+        )?;
+        writeln!(
+            file,
+            "library: {} {}",
+            job.get_library_info().get_name(),
+            job.get_library_info().get_version()
+        )?;
+        writeln!(file, "error: {}", error.get_message())?;
+        if output.truncated {
+            writeln!(file, "(the output was truncated)")?;
+        }
+        writeln!(file, "\n--- stdout ---\n{}", output.stdout)?;
+        writeln!(file, "--- stderr ---\n{}", output.stderr)?;
+        Ok(())
+    }
+
+    fn write_record(file: &mut File, record: &str) -> Result<(), Box<dyn Error>> {
+        // NOTE: The record is written at once so that the readers never see a part of it.
+        file.write_all(record.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+
+    fn format_record(format: ResultFormat, result: &JobResult) -> Result<String, Box<dyn Error>> {
+        Ok(match format {
+            ResultFormat::Toml => toml::to_string(&Record {
+                results: std::slice::from_ref(result),
+            })?,
+            ResultFormat::JsonLines => format!("{}\n", serde_json::to_string(result)?),
+        })
+    }
+
+    pub fn write(&self, result: &JobResult) -> Result<(), Box<dyn Error>> {
+        let record = Self::format_record(self.format, result)?;
+        {
+            let mut state = self.state.lock().unwrap();
+            Self::write_record(&mut state.file, &record)?;
+            state.summary.add(result.get_status());
+        }
+        self.write_job_log(result)
+    }
+
+    /// Replaces the output with the sorted results and the summary at the end, and returns the
+    /// summary.
+    pub fn finish(self) -> Result<Summary, Box<dyn Error>> {
+        let state = self.state.into_inner().unwrap();
+        drop(state.file);
+        // NOTE: The results were written in the order the jobs finished, which depends on the
+        // threads. The sorted output is written next to the streamed one and renamed over it, so
+        // that the streamed results survive if the rewrite fails.
+        let mut results = read_results(&self.path)?;
+        sort_results(&mut results);
+        let dir = match self.path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let mut sorted = NamedTempFile::new_in(dir)?;
+        for r in &results {
+            Self::write_record(sorted.as_file_mut(), &Self::format_record(self.format, r)?)?;
+        }
+        let footer = Footer {
+            summary: &state.summary,
+        };
+        let record = match self.format {
+            ResultFormat::Toml => format!("\n{}", toml::to_string(&footer)?),
+            ResultFormat::JsonLines => format!("{}\n", serde_json::to_string(&footer)?),
+        };
+        Self::write_record(sorted.as_file_mut(), &record)?;
+        fs::set_permissions(sorted.path(), fs::metadata(&self.path)?.permissions())?;
+        sorted.persist(&self.path)?;
+        Ok(state.summary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::clone_pair::{ClonePair, CodePosition, CodeSlice};
+    use crate::error::RunnerProcessFailedError;
+    use crate::job::test_job;
+    use crate::output::{read_results, ResultFormat, ResultWriter, Summary};
+    use crate::runner::process::CapturedOutput;
+
+    fn pair(line: u32) -> ClonePair {
+        let slice = CodeSlice::new(CodePosition::new(line, 1), CodePosition::new(line + 1, 1));
+        ClonePair::new(slice.clone(), 0.5, slice, 0.5)
+    }
+
+    #[test]
+    fn test_write_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("result.toml");
+        let writer = ResultWriter::create(&path, ResultFormat::Toml).unwrap();
+        writer
+            .write(&test_job("Example/Example.ino").create_result(Vec::new()))
+            .unwrap();
+        writer
+            .write(&test_job("Example/Example.ino").create_skipped_result())
+            .unwrap();
+        let summary = writer.finish().unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                total: 2,
+                ok: 1,
+                skipped: 1,
+                ..Summary::default()
+            }
+        );
+
+        // The records form a single TOML document.
+        let document: toml::Value = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(document["results"].as_array().unwrap().len(), 2);
+        assert_eq!(document["summary"]["total"].as_integer(), Some(2));
+        assert_eq!(Summary::of(&read_results(&path).unwrap()), summary);
+    }
+
+    #[test]
+    fn test_write_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("result.jsonl");
+        let writer = ResultWriter::create(&path, ResultFormat::JsonLines).unwrap();
+        writer
+            .write(&test_job("Example/Example.ino").create_result(Vec::new()))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["status"], "ok");
+        assert_eq!(lines[1]["summary"]["ok"], 1);
+        assert_eq!(read_results(&path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_write_job_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let writer = ResultWriter::create(&dir.path().join("result.toml"), ResultFormat::Toml)
+            .unwrap()
+            .with_job_log_path(&dir.path().join("logs"))
+            .unwrap();
+        let error = RunnerProcessFailedError::new(1).with_output(CapturedOutput {
+            stdout: String::from("parsing src"),
+            stderr: String::from("syntax error"),
+            truncated: false,
+        });
+        writer
+            .write(&test_job("Example/Example.ino").create_error_result(&error))
+            .unwrap();
+        writer
+            .write(&test_job("Example/Example.ino").create_skipped_result())
+            .unwrap();
+        writer.finish().unwrap();
+
+        let logs: Vec<_> = fs::read_dir(dir.path().join("logs")).unwrap().collect();
+        assert_eq!(logs.len(), 1);
+        let log = fs::read_to_string(
+            dir.path()
+                .join("logs")
+                .join(format!("{}.log", test_job("Example/Example.ino").get_id())),
+        )
+        .unwrap();
+        assert!(log.contains("parsing src"));
+        assert!(log.contains("syntax error"));
+    }
+
+    #[test]
+    fn test_sorted_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = test_job("A/A.ino").create_result(vec![pair(9), pair(3)]);
+        let b = test_job("B/B.ino").create_skipped_result();
+        let mut outputs = Vec::new();
+        for (i, results) in [[&a, &b], [&b, &a]].iter().enumerate() {
+            let path = dir.path().join(format!("result{}.toml", i));
+            let writer = ResultWriter::create(&path, ResultFormat::Toml).unwrap();
+            for r in results {
+                writer.write(r).unwrap();
+            }
+            writer.finish().unwrap();
+            outputs.push(fs::read_to_string(&path).unwrap());
+        }
+        assert_eq!(outputs[0], outputs[1]);
+
+        let results = read_results(&dir.path().join("result0.toml")).unwrap();
+        assert_eq!(results[0].get_job().get_id(), a.get_job().get_id());
+        assert_eq!(results[0].get_clone_pairs(), &[pair(3), pair(9)]);
+    }
+}
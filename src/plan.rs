@@ -0,0 +1,197 @@
+use std::path::Path;
+
+use serde_derive::Serialize;
+
+use crate::job::Job;
+use crate::runner::archive::ArchiveCache;
+use crate::runner::Runner;
+
+/// A job and where its sources are read from.
+#[derive(Debug, Serialize)]
+pub struct PlannedJob {
+    id: String,
+    project: String,
+    library_archive: String,
+    example_entry: String,
+    /// The reasons why the sources could not be resolved.
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedBatch {
+    jobs: Vec<PlannedJob>,
+    commands: Vec<String>,
+}
+
+/// What a run would do, made without running the detector.
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    detector: String,
+    number_of_threads: usize,
+    number_of_jobs: usize,
+    number_of_unresolved_jobs: usize,
+    batches: Vec<PlannedBatch>,
+}
+
+fn resolve_job(job: &Job, project_path: &Path, archives: &ArchiveCache) -> PlannedJob {
+    let mut errors = Vec::new();
+    let project = match job.get_project().get_location_from(project_path) {
+        Ok(p) => p,
+        Err(e) => {
+            errors.push(format!("project: {}", e));
+            job.get_project().get_non_canonical_path_from(project_path)
+        }
+    };
+    let library_archive = match job
+        .get_library_info()
+        .get_absolute_location(archives.get_database_path())
+    {
+        Ok(p) => p,
+        Err(e) => {
+            errors.push(format!("library archive: {}", e));
+            archives
+                .get_database_path()
+                .join("libraries")
+                .join(job.get_library_info().get_location())
+        }
+    };
+    let example_entry = ArchiveCache::get_example_entry_path(job);
+    if errors.is_empty() {
+        if let Err(e) = archives.read_example_sketch(job) {
+            errors.push(format!("example sketch: {}", e));
+        }
+    }
+    PlannedJob {
+        id: job.get_id(),
+        project: String::from(project.to_str().unwrap()),
+        library_archive: String::from(library_archive.to_str().unwrap()),
+        example_entry: String::from(example_entry.to_str().unwrap()),
+        errors,
+    }
+}
+
+impl Plan {
+    pub fn create<R>(
+        detector: &str,
+        number_of_threads: usize,
+        batches: &[Vec<Job>],
+        runner: &R,
+        project_path: &Path,
+        archives: &ArchiveCache,
+    ) -> Self
+    where
+        R: Runner + ?Sized,
+    {
+        let batches: Vec<PlannedBatch> = batches
+            .iter()
+            .map(|b| PlannedBatch {
+                jobs: b
+                    .iter()
+                    .map(|j| resolve_job(j, project_path, archives))
+                    .collect(),
+                commands: runner.get_command_lines(b),
+            })
+            .collect();
+        let jobs = batches.iter().flat_map(|b| &b.jobs);
+        Plan {
+            detector: String::from(detector),
+            number_of_threads,
+            number_of_jobs: jobs.clone().count(),
+            number_of_unresolved_jobs: jobs.filter(|j| !j.errors.is_empty()).count(),
+            batches,
+        }
+    }
+
+    pub fn has_unresolved_jobs(&self) -> bool {
+        self.number_of_unresolved_jobs > 0
+    }
+
+    pub fn print(&self) {
+        println!(
+            "Detector: {} on {} thread(s)",
+            self.detector, self.number_of_threads
+        );
+        for (i, batch) in self.batches.iter().enumerate() {
+            println!("Batch {}/{}:", i + 1, self.batches.len());
+            for job in &batch.jobs {
+                println!("  Job {}", job.id);
+                println!("    project: {}", job.project);
+                println!("    library archive: {}", job.library_archive);
+                println!("    example entry: {}", job.example_entry);
+                for e in &job.errors {
+                    println!("    UNRESOLVED {}", e);
+                }
+            }
+            for command in &batch.commands {
+                println!("  $ {}", command);
+            }
+        }
+        println!(
+            "Planned {} job(s) in {} batch(es); {} job(s) would fail resolution.",
+            self.number_of_jobs,
+            self.batches.len(),
+            self.number_of_unresolved_jobs
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use crate::clone_pair::ClonePair;
+    use crate::job::{test_job, Job};
+    use crate::plan::Plan;
+    use crate::runner::archive::ArchiveCache;
+    use crate::runner::Runner;
+
+    struct EchoRunner;
+
+    impl Runner for EchoRunner {
+        fn run_job(&self, _job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+            unreachable!()
+        }
+
+        fn get_command_lines(&self, jobs: &[Job]) -> Vec<String> {
+            vec![format!("echo {}", jobs.len())]
+        }
+    }
+
+    #[test]
+    fn test_create_plan() {
+        let database = tempfile::tempdir().unwrap();
+        let library_path = database.path().join("libraries/Library/1.0.0");
+        fs::create_dir_all(&library_path).unwrap();
+        let mut zip = ZipWriter::new(File::create(library_path.join("Library-1.0.0.zip")).unwrap());
+        zip.start_file(
+            "Library-1.0.0/examples/Example/Example.ino",
+            FileOptions::default(),
+        )
+        .unwrap();
+        write!(zip, "void setup() {{}}").unwrap();
+        zip.finish().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        File::create(project.path().join("MyProject.ino")).unwrap();
+
+        let archives = ArchiveCache::new(database.path(), 1024);
+        let batches = vec![vec![
+            test_job("Example/Example.ino"),
+            test_job("Missing/Missing.ino"),
+        ]];
+        let plan = Plan::create("Test", 2, &batches, &EchoRunner, project.path(), &archives);
+        assert_eq!(plan.number_of_jobs, 2);
+        assert_eq!(plan.number_of_unresolved_jobs, 1);
+        assert!(plan.batches[0].jobs[0].errors.is_empty());
+        assert_eq!(
+            plan.batches[0].jobs[1].example_entry,
+            "Library-1.0.0/examples/Missing/Missing.ino"
+        );
+        assert_eq!(plan.batches[0].commands, vec!["echo 2"]);
+    }
+}
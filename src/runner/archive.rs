@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use log::{debug, error};
+
+use zip::ZipArchive;
+
+use crate::job::Job;
+
+/// The number of library archives kept open at once.
+const MAX_OPEN_ARCHIVES: usize = 64;
+
+/// A least recently used map whose entries are weighted (e.g. by their size in bytes).
+struct Lru<K, V> {
+    entries: HashMap<K, (V, usize, u64)>,
+    total_weight: usize,
+    tick: u64,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Lru<K, V> {
+    fn new() -> Self {
+        Lru {
+            entries: HashMap::new(),
+            total_weight: 0,
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|(value, _, last_used)| {
+            *last_used = tick;
+            value.clone()
+        })
+    }
+
+    /// Inserts the entry and evicts the least recently used ones until the total weight fits in
+    /// the limit. An entry heavier than the limit is not inserted.
+    fn insert(&mut self, key: K, value: V, weight: usize, limit: usize) {
+        if weight > limit {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, old_weight, _)) = self.entries.insert(key, (value, weight, self.tick)) {
+            self.total_weight -= old_weight;
+        }
+        self.total_weight += weight;
+        while self.total_weight > limit {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, _, last_used))| *last_used)
+                .map(|(k, _)| k.clone())
+                .unwrap();
+            let (_, weight, _) = self.entries.remove(&oldest).unwrap();
+            self.total_weight -= weight;
+        }
+    }
+}
+
+type SharedArchive = Arc<Mutex<ZipArchive<File>>>;
+
+/// Reads the example sketches from the library archives in the Munin database.
+///
+/// The archives are indexed once and shared by all threads, and the extracted sources are kept
+/// in memory up to `size_limit` bytes.
+pub struct ArchiveCache {
+    database_path: PathBuf,
+    size_limit: usize,
+    archives: Mutex<Lru<PathBuf, SharedArchive>>,
+    contents: Mutex<Lru<(PathBuf, PathBuf), String>>,
+}
+
+impl ArchiveCache {
+    pub fn new(database_path: &Path, size_limit: usize) -> Self {
+        ArchiveCache {
+            database_path: PathBuf::from(database_path),
+            size_limit,
+            archives: Mutex::new(Lru::new()),
+            contents: Mutex::new(Lru::new()),
+        }
+    }
+
+    fn open_archive(&self, archive_path: &Path) -> Result<SharedArchive, Box<dyn Error>> {
+        let mut archives = self.archives.lock().unwrap();
+        if let Some(archive) = archives.get(&PathBuf::from(archive_path)) {
+            return Ok(archive);
+        }
+        debug!(
+            "Opening the library archive...: {}",
+            archive_path.to_str().unwrap()
+        );
+        let archive = Arc::new(Mutex::new(ZipArchive::new(File::open(archive_path)?)?));
+        archives.insert(
+            PathBuf::from(archive_path),
+            archive.clone(),
+            1,
+            MAX_OPEN_ARCHIVES,
+        );
+        Ok(archive)
+    }
+
+    pub fn get_database_path(&self) -> &Path {
+        &self.database_path
+    }
+
+    /// Returns the path of the example sketch in the library archive.
+    pub fn get_example_entry_path(job: &Job) -> PathBuf {
+        job.get_example_sketch().get_non_canonical_path_from(
+            &Path::new(job.get_library_info().get_archive_root()).join("examples"),
+        )
+    }
+
+    pub fn read_example_sketch(&self, job: &Job) -> Result<String, Box<dyn Error>> {
+        let library_archive_path = job
+            .get_library_info()
+            .get_absolute_location(&self.database_path)?;
+        let example_path = Self::get_example_entry_path(job);
+        let key = (library_archive_path, example_path);
+        if let Some(contents) = self.contents.lock().unwrap().get(&key) {
+            return Ok(contents);
+        }
+
+        let (library_archive_path, example_path) = key;
+        let archive = self.open_archive(&library_archive_path)?;
+        debug!(
+            "Searching the source file: {}",
+            example_path.to_str().unwrap()
+        );
+        let mut contents = String::new();
+        {
+            let mut archive = archive.lock().unwrap();
+            let mut file = match archive.by_name(example_path.to_str().unwrap()) {
+                Ok(f) => f,
+                Err(e) => {
+                    error!(
+                        "Could not open an example sketch source: {}",
+                        job.get_example_sketch()
+                            .get_non_canonical_path_from(Path::new(""))
+                            .to_str()
+                            .unwrap()
+                    );
+                    return Err(e.into());
+                }
+            };
+            file.read_to_string(&mut contents)?;
+        }
+        self.contents.lock().unwrap().insert(
+            (library_archive_path, example_path),
+            contents.clone(),
+            contents.len(),
+            self.size_limit,
+        );
+        Ok(contents)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use crate::job::test_job;
+    use crate::runner::archive::{ArchiveCache, Lru};
+
+    #[test]
+    fn test_lru() {
+        let mut lru = Lru::new();
+        lru.insert("a", 1, 2, 4);
+        lru.insert("b", 2, 2, 4);
+        assert_eq!(lru.get(&"a"), Some(1));
+        lru.insert("c", 3, 2, 4);
+        assert_eq!(lru.get(&"b"), None);
+        assert_eq!(lru.get(&"a"), Some(1));
+        lru.insert("d", 4, 5, 4);
+        assert_eq!(lru.get(&"d"), None);
+    }
+
+    #[test]
+    fn test_read_example_sketch() {
+        let database = tempfile::tempdir().unwrap();
+        let library_path = database.path().join("libraries/Library/1.0.0");
+        fs::create_dir_all(&library_path).unwrap();
+        let archive_path = library_path.join("Library-1.0.0.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path).unwrap());
+        zip.start_file(
+            "Library-1.0.0/examples/Example/Example.ino",
+            FileOptions::default(),
+        )
+        .unwrap();
+        write!(zip, "void setup() {{}}").unwrap();
+        zip.finish().unwrap();
+        let job = test_job("Example/Example.ino");
+
+        let cache = ArchiveCache::new(database.path(), 1024);
+        let uncached = ArchiveCache::new(database.path(), 0);
+        assert_eq!(cache.read_example_sketch(&job).unwrap(), "void setup() {}");
+        assert_eq!(
+            uncached.read_example_sketch(&job).unwrap(),
+            "void setup() {}"
+        );
+
+        // The extracted source is served from the memory even if the archive is broken.
+        File::create(&archive_path).unwrap();
+        assert_eq!(cache.read_example_sketch(&job).unwrap(), "void setup() {}");
+        assert!(uncached.read_example_sketch(&job).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use crate::config::BatchKey;
+use crate::job::Job;
+
+fn get_batch_key(job: &Job, key: &BatchKey) -> String {
+    match key {
+        BatchKey::Project => String::from(job.get_project().get_location()),
+        BatchKey::Library => String::from(job.get_library_info().get_location()),
+    }
+}
+
+/// Groups the jobs sharing the project file (or the library) into batches of at most
+/// `batch_size` jobs.
+///
+/// The batches are ordered by their first job in `jobs`.
+pub fn group_jobs(jobs: &[Job], batch_size: usize, key: &BatchKey) -> Vec<Vec<Job>> {
+    let batch_size = batch_size.max(1);
+    let mut groups: Vec<Vec<Job>> = Vec::new();
+    let mut group_indices: HashMap<String, usize> = HashMap::new();
+    for j in jobs {
+        let index = *group_indices
+            .entry(get_batch_key(j, key))
+            .or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+        groups[index].push(j.clone());
+    }
+    groups
+        .iter()
+        .flat_map(|g| g.chunks(batch_size).map(|c| c.to_vec()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::BatchKey;
+    use crate::job::{test_job, Job};
+    use crate::runner::batch::group_jobs;
+
+    fn job(project: &str, library: &str, example: &str) -> Job {
+        test_job(example)
+            .with_project(project)
+            .with_library(library)
+    }
+
+    fn examples(batch: &[Job]) -> Vec<&str> {
+        batch
+            .iter()
+            .map(|j| j.get_example_sketch().get_location())
+            .collect()
+    }
+
+    #[test]
+    fn test_group_jobs_by_project() {
+        let jobs = vec![
+            job("a.ino", "LibA", "A1/A1.ino"),
+            job("b.ino", "LibA", "A2/A2.ino"),
+            job("a.ino", "LibB", "B1/B1.ino"),
+            job("a.ino", "LibB", "B2/B2.ino"),
+        ];
+        let batches = group_jobs(&jobs, 2, &BatchKey::Project);
+        let batches: Vec<Vec<&str>> = batches.iter().map(|b| examples(b)).collect();
+        assert_eq!(
+            batches,
+            vec![
+                vec!["A1/A1.ino", "B1/B1.ino"],
+                vec!["B2/B2.ino"],
+                vec!["A2/A2.ino"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_jobs_by_library() {
+        let jobs = vec![
+            job("a.ino", "LibA", "A1/A1.ino"),
+            job("b.ino", "LibB", "B1/B1.ino"),
+            job("c.ino", "LibA", "A2/A2.ino"),
+        ];
+        let batches = group_jobs(&jobs, 10, &BatchKey::Library);
+        let batches: Vec<Vec<&str>> = batches.iter().map(|b| examples(b)).collect();
+        assert_eq!(
+            batches,
+            vec![vec!["A1/A1.ino", "A2/A2.ino"], vec!["B1/B1.ino"]]
+        );
+        assert_eq!(group_jobs(&jobs, 0, &BatchKey::Library).len(), 3);
+    }
+}
@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::{debug, warn};
+
+use crate::cache::ResultCache;
+use crate::clone_pair::ClonePair;
+use crate::job::Job;
+use crate::runner::archive::ArchiveCache;
+use crate::runner::{read_example_sketch, JobOutcome, Runner};
+
+/// Returns the clone pairs from the cache for the jobs whose inputs didn't change, and runs the
+/// other jobs on the inner runner.
+pub struct CachingRunner {
+    inner: Box<dyn Runner + Sync + Send>,
+    cache: ResultCache,
+    detector_digest: String,
+    project_path: PathBuf,
+    archives: Arc<ArchiveCache>,
+}
+
+impl CachingRunner {
+    pub fn create(
+        inner: Box<dyn Runner + Sync + Send>,
+        cache: ResultCache,
+        detector_digest: String,
+        project_path: &Path,
+        archives: Arc<ArchiveCache>,
+    ) -> Self {
+        CachingRunner {
+            inner,
+            cache,
+            detector_digest,
+            project_path: PathBuf::from(project_path),
+            archives,
+        }
+    }
+
+    fn get_key(&self, job: &Job) -> Result<String, Box<dyn Error>> {
+        let project_source =
+            std::fs::read(job.get_project().get_location_from(&self.project_path)?)?;
+        let example_source = read_example_sketch(job, &self.archives)?;
+        Ok(ResultCache::get_key(
+            &self.detector_digest,
+            &project_source,
+            example_source.as_bytes(),
+        ))
+    }
+
+    /// Returns the key of the job and the cached clone pairs if any.
+    ///
+    /// The jobs whose sources can't be read are left to the inner runner, which reports the
+    /// error.
+    fn look_up(&self, job: &Job) -> (Option<String>, Option<Vec<ClonePair>>) {
+        match self.get_key(job) {
+            Ok(key) => {
+                let cached = self.cache.get(&key);
+                (Some(key), cached)
+            }
+            Err(e) => {
+                debug!("Could not compute the cache key: {}", e);
+                (None, None)
+            }
+        }
+    }
+
+    fn store(&self, key: Option<String>, outcome: &JobOutcome) {
+        if let (Some(key), Ok(pairs)) = (key, outcome) {
+            if let Err(e) = self.cache.put(&key, pairs) {
+                warn!("Could not store the result in the cache: {}", e);
+            }
+        }
+    }
+}
+
+impl Runner for CachingRunner {
+    fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let (key, cached) = self.look_up(&job);
+        if let Some(pairs) = cached {
+            return Ok(pairs);
+        }
+        let outcome = self.inner.run_job(job);
+        self.store(key, &outcome);
+        outcome
+    }
+
+    fn run_batch(&self, jobs: &[Job]) -> Vec<JobOutcome> {
+        let mut results: Vec<Option<JobOutcome>> = Vec::new();
+        let mut missed_jobs = Vec::new();
+        let mut missed_keys = Vec::new();
+        for job in jobs {
+            let (key, cached) = self.look_up(job);
+            match cached {
+                Some(pairs) => results.push(Some(Ok(pairs))),
+                None => {
+                    results.push(None);
+                    missed_jobs.push(job.clone());
+                    missed_keys.push(key);
+                }
+            }
+        }
+        debug!(
+            "Found {} of {} job(s) in the cache.",
+            jobs.len() - missed_jobs.len(),
+            jobs.len()
+        );
+        if !missed_jobs.is_empty() {
+            // The missed jobs are still run together to keep the batching of the inner runner.
+            let outcomes = self.inner.run_batch(&missed_jobs);
+            let missed_results = results.iter_mut().filter(|r| r.is_none());
+            for ((r, key), outcome) in missed_results.zip(missed_keys).zip(outcomes) {
+                self.store(key, &outcome);
+                *r = Some(outcome);
+            }
+        }
+        results.into_iter().map(|r| r.unwrap()).collect()
+    }
+
+    // NOTE: The cache is not looked up here, so the command lines of the cached jobs are
+    // included.
+    fn get_command_lines(&self, jobs: &[Job]) -> Vec<String> {
+        self.inner.get_command_lines(jobs)
+    }
+}
@@ -1,143 +1,426 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
 use std::fs::File;
 use std::io::Read;
-use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
 
-use log::{debug, error, info, warn};
+use log::{debug, error, warn};
 
-use zip::ZipArchive;
-
-mod parser;
+pub(crate) mod parser;
+mod worker;
 
 use crate::clone_pair::ClonePair;
 use crate::config::ccfindersw::CCFinderSWConfig;
-use crate::error::RunnerProcessFailedError;
+use crate::config::JobTimeouts;
+use crate::error::{
+    BatchFailedError, JobCancelledError, JobTimedOutError, RunnerProcessFailedError,
+};
 use crate::job::Job;
-use crate::runner::ccfindersw::parser::ResultParser;
-use crate::runner::Runner;
+use crate::runner::archive::ArchiveCache;
+use crate::runner::ccfindersw::parser::{ParsedResult, ResultParser};
+use crate::runner::ccfindersw::worker::WorkerPool;
+use crate::runner::process::run_with_timeout;
+use crate::runner::workdir::{format_command, record_command, WorkDirs, WORKING_DIR_PLACEHOLDER};
+use crate::runner::{
+    stage_example_sketch, stage_project_source, stage_sources, JobOutcome, Runner,
+};
 
 #[derive(Clone)]
 pub struct CCFinderSWRunner {
     project_path: PathBuf,
-    database_path: PathBuf,
+    archives: Arc<ArchiveCache>,
     config: CCFinderSWConfig,
+    timeouts: JobTimeouts,
+    work_dirs: Arc<WorkDirs>,
+    workers: Option<Arc<WorkerPool>>,
 }
 
 impl CCFinderSWRunner {
-    pub fn create(config: CCFinderSWConfig, project_path: &Path, database_path: &Path) -> Self {
+    pub fn create(
+        config: CCFinderSWConfig,
+        timeouts: JobTimeouts,
+        project_path: &Path,
+        archives: Arc<ArchiveCache>,
+        work_dirs: Arc<WorkDirs>,
+    ) -> Self {
+        let workers = config
+            .get_worker_config()
+            .map(|c| Arc::new(WorkerPool::new(c.clone())));
         CCFinderSWRunner {
             project_path: PathBuf::from(project_path),
-            database_path: PathBuf::from(database_path),
+            archives,
             config,
+            timeouts,
+            work_dirs,
+            workers,
         }
     }
-}
 
-impl Runner for CCFinderSWRunner {
-    fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
-        let project_source_name = job.project.get_file_name()?;
-        let example_source_name = job.example_sketch.get_file_name()?;
-        let library_info = job.library_info;
-        let library_archive_path = library_info.get_absolute_location(&self.database_path)?;
+    fn get_detector_arguments(&self, sources_path: &str, output_path: &str) -> Vec<String> {
+        [
+            "D",
+            "-d",
+            sources_path,
+            "-l",
+            &self.config.language_to_option_value(),
+            "-o",
+            output_path,
+            "-t",
+            &self.config.token_length_to_option_value(),
+            "-w",
+            "2",
+            "-antlr",
+            &self.config.extensions_to_option_value(),
+            "-charset",
+            "auto",
+        ]
+        .iter()
+        .map(|a| String::from(*a))
+        .collect()
+    }
 
-        let working_dir = tempfile::tempdir()?;
-        let sources_path = working_dir.path().join("src");
-        fs::create_dir(&sources_path)?;
+    fn create_command(&self, working_dir: &Path) -> Command {
+        let mut command = Command::new(self.config.get_executable_path_as_string());
+        command
+            .current_dir(working_dir)
+            .args(self.get_detector_arguments("src", "result"));
+        command
+    }
 
-        debug!(
-            "Copying the project source file...: {}",
-            job.project
-                .get_location_from(&self.project_path)?
-                .to_str()
-                .unwrap()
-        );
-        fs::copy(
-            job.project.get_location_from(&self.project_path)?,
-            sources_path.join(project_source_name.clone()),
-        )?;
+    fn spawn_detector(
+        &self,
+        command: &mut Command,
+        timeout: Option<Duration>,
+    ) -> Result<(), Box<dyn Error>> {
+        let res = run_with_timeout(command, timeout)?;
+        if res.status.success() {
+            Ok(())
+        } else {
+            Err(
+                RunnerProcessFailedError::new(res.status.code().unwrap_or(-1))
+                    .with_output(res.output)
+                    .into(),
+            )
+        }
+    }
 
-        {
-            let mut example_source = File::create(sources_path.join(example_source_name.clone()))?;
-            debug!(
-                "Opening the library archive...: {}",
-                library_archive_path.to_str().unwrap()
-            );
-            let library_zip = File::open(library_archive_path)?;
-            let mut library_archive = ZipArchive::new(library_zip)?;
-            debug!(
-                "Searching the source file: {}",
-                job.example_sketch
-                    .get_non_canonical_path_from(
-                        &Path::new(library_info.archive_root.as_str()).join("examples")
-                    )
-                    .to_str()
-                    .unwrap()
-            );
-            let mut file = match library_archive.by_name(
-                job.example_sketch
-                    .get_non_canonical_path_from(
-                        &Path::new(library_info.archive_root.as_str()).join("examples"),
-                    )
-                    .to_str()
-                    .unwrap(),
-            ) {
-                Ok(f) => f,
-                Err(e) => {
-                    error!(
-                        "Could not open an example sketch source: {}",
-                        job.example_sketch
-                            .get_non_canonical_path_from(&Path::new(""))
-                            .to_str()
-                            .unwrap()
-                    );
-                    return Err(e.into());
+    /// Runs CCFinderSW on the `src` directory in the working directory and parses the result.
+    ///
+    /// With the worker mode, a warm worker is tried first and CCFinderSW is spawned only if it
+    /// fails. A job which timed out on the worker is not retried.
+    fn run_detector(
+        &self,
+        working_dir: &Path,
+        timeout: Option<Duration>,
+    ) -> Result<ParsedResult, Box<dyn Error>> {
+        let mut command = self.create_command(working_dir);
+        record_command(working_dir, &command)?;
+        let worker_succeeded = match &self.workers {
+            Some(workers) => {
+                // NOTE: The worker can't change its working directory, so the paths must be
+                // absolute.
+                let args = self.get_detector_arguments(
+                    working_dir.join("src").to_str().unwrap(),
+                    working_dir.join("result").to_str().unwrap(),
+                );
+                match workers.run(&args, timeout) {
+                    Ok(()) => true,
+                    Err(e) if e.is::<JobTimedOutError>() || e.is::<JobCancelledError>() => {
+                        return Err(e)
+                    }
+                    Err(e) => {
+                        warn!("Falling back to spawning CCFinderSW: {}", e);
+                        false
+                    }
                 }
-            };
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)?;
-            write!(example_source, "{}", contents)?;
-
-            let status = Command::new(self.config.get_executable_path_as_string())
-                .current_dir(&working_dir)
-                .args(&[
-                    "D",
-                    "-d",
-                    "src",
-                    "-l",
-                    &self.config.language_to_option_value(),
-                    "-o",
-                    "result",
-                    "-t",
-                    &self.config.token_length_to_option_value(),
-                    "-w",
-                    "2",
-                    "-antlr",
-                    &self.config.extensions_to_option_value(),
-                    "-charset",
-                    "auto",
-                ])
-                .stderr(Stdio::null())
-                .stdout(Stdio::null())
-                .stdin(Stdio::null())
-                .spawn()?
-                .wait()?;
-            if status.success() {
-                let mut file = File::open(&working_dir.path().join("result.txt"))?;
-                let mut contents = String::new();
-                file.read_to_string(&mut contents)?;
-                let parser = ResultParser::new();
-                let (_, parse_result) = parser.parse_result::<()>(&contents)?;
+            }
+            None => false,
+        };
+        if !worker_succeeded {
+            self.spawn_detector(&mut command, timeout)?;
+        }
+        let mut file = File::open(working_dir.join("result.txt"))?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let parser = ResultParser::new();
+        let (_, parse_result) = parser.parse_result::<()>(&contents)?;
+        Ok(parse_result)
+    }
+
+    fn stage_batch_job(
+        &self,
+        job: &Job,
+        index: usize,
+        sources_path: &Path,
+        project_source_names: &mut HashMap<String, String>,
+    ) -> Result<(String, String), Box<dyn Error>> {
+        let project_location = String::from(job.get_project().get_location());
+        let project_source_name = match project_source_names.get(&project_location) {
+            Some(name) => name.clone(),
+            None => {
+                let name = format!(
+                    "p{}_{}",
+                    project_source_names.len(),
+                    job.get_project().get_file_name()?
+                );
+                stage_project_source(job, &self.project_path, sources_path, &name)?;
+                project_source_names.insert(project_location, name.clone());
+                name
+            }
+        };
+        let example_source_name =
+            format!("e{}_{}", index, job.get_example_sketch().get_file_name()?);
+        stage_example_sketch(job, &self.archives, sources_path, &example_source_name)?;
+        Ok((project_source_name, example_source_name))
+    }
+
+    /// Stages the sources of all jobs in one directory and runs CCFinderSW once.
+    ///
+    /// The staged files are prefixed so that the files with the same name don't collide. The
+    /// jobs which could not be staged fail alone.
+    fn run_staged_batch(&self, jobs: &[Job]) -> Result<Vec<JobOutcome>, Box<dyn Error>> {
+        let working_dir = self.work_dirs.create()?;
+        let res = self.run_staged_batch_in(jobs, working_dir.path());
+        let succeeded = match &res {
+            Ok(outcomes) => outcomes.iter().all(|o| o.is_ok()),
+            Err(_) => false,
+        };
+        self.work_dirs.finish(working_dir, jobs, succeeded);
+        res
+    }
+
+    fn run_staged_batch_in(
+        &self,
+        jobs: &[Job],
+        working_dir: &Path,
+    ) -> Result<Vec<JobOutcome>, Box<dyn Error>> {
+        let sources_path = working_dir.join("src");
+        fs::create_dir(&sources_path)?;
+
+        let mut project_source_names: HashMap<String, String> = HashMap::new();
+        let mut staged_jobs = Vec::new();
+        for (i, job) in jobs.iter().enumerate() {
+            staged_jobs.push(self.stage_batch_job(
+                job,
+                i,
+                &sources_path,
+                &mut project_source_names,
+            ));
+        }
+        if staged_jobs.iter().all(|s| s.is_err()) {
+            return Ok(staged_jobs
+                .into_iter()
+                .map(|s| s.map(|_| Vec::new()))
+                .collect());
+        }
+
+        debug!("Running CCFinderSW on a batch of {} job(s)...", jobs.len());
+        let parse_result = self.run_detector(working_dir, self.timeouts.get_batch_timeout(jobs))?;
+        Ok(staged_jobs
+            .into_iter()
+            .map(|s| {
+                let (project_source_name, example_source_name) = s?;
                 let clone_pairs = parse_result
                     .get_clone_pairs(project_source_name.as_str(), example_source_name.as_str())?;
                 debug!("pairs: {:?}", clone_pairs);
-                return Ok(clone_pairs);
-            } else {
-                return Err(RunnerProcessFailedError::new(status.code().unwrap()).into());
+                Ok(clone_pairs)
+            })
+            .collect())
+    }
+
+    fn run_job_in(&self, job: &Job, working_dir: &Path) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let project_source_name = job.get_project().get_file_name()?;
+        let example_source_name = job.get_example_sketch().get_file_name()?;
+
+        let sources_path = working_dir.join("src");
+        fs::create_dir(&sources_path)?;
+
+        stage_sources(
+            job,
+            &self.project_path,
+            &self.archives,
+            &sources_path,
+            &project_source_name,
+            &example_source_name,
+        )?;
+
+        let parse_result = self.run_detector(
+            working_dir,
+            self.timeouts.get_timeout(job.get_library_info()),
+        )?;
+        let clone_pairs = parse_result
+            .get_clone_pairs(project_source_name.as_str(), example_source_name.as_str())?;
+        debug!("pairs: {:?}", clone_pairs);
+        Ok(clone_pairs)
+    }
+}
+
+impl Runner for CCFinderSWRunner {
+    fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let working_dir = self.work_dirs.create()?;
+        let res = self.run_job_in(&job, working_dir.path());
+        self.work_dirs
+            .finish(working_dir, std::slice::from_ref(&job), res.is_ok());
+        res
+    }
+
+    fn run_batch(&self, jobs: &[Job]) -> Vec<JobOutcome> {
+        if jobs.len() == 1 {
+            return vec![self.run_job(jobs[0].clone())];
+        }
+        match self.run_staged_batch(jobs) {
+            Ok(results) => results,
+            Err(e) => {
+                error!("A batch of {} job(s) failed: {}", jobs.len(), e);
+                jobs.iter()
+                    .map(|_| -> JobOutcome {
+                        if let Some(t) = e.downcast_ref::<JobTimedOutError>() {
+                            Err(t.clone().into())
+                        } else if let Some(c) = e.downcast_ref::<JobCancelledError>() {
+                            Err(c.clone().into())
+                        } else {
+                            let output = e
+                                .downcast_ref::<RunnerProcessFailedError>()
+                                .and_then(|e| e.get_output().cloned());
+                            Err(BatchFailedError::new(e.to_string().as_str())
+                                .with_output(output)
+                                .into())
+                        }
+                    })
+                    .collect()
             }
         }
     }
+
+    fn get_command_lines(&self, _jobs: &[Job]) -> Vec<String> {
+        // NOTE: The jobs in a batch share a single invocation.
+        vec![format_command(
+            &self.create_command(Path::new(WORKING_DIR_PLACEHOLDER)),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Arc;
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use crate::config::ccfindersw::CCFinderSWConfig;
+    use crate::config::Config;
+    use crate::job::test_job;
+    use crate::runner::archive::ArchiveCache;
+    use crate::runner::ccfindersw::CCFinderSWRunner;
+    use crate::runner::workdir::{KeepWorkDirs, WorkDirs};
+    use crate::runner::Runner;
+
+    /// Reports a clone between the project and each example, one among all files and one among
+    /// the examples only, like CCFinderSW on the staged sources.
+    const STUB_CCFINDERSW: &str = r#"#!/bin/sh
+cd src
+files=$(ls)
+examples=0
+i=0
+for f in $files; do
+    case $f in
+        *MyProject*) project=$i ;;
+        *) examples=$((examples + 1)) ;;
+    esac
+    i=$((i + 1))
+done
+{
+    echo '#begin{file description}'
+    i=0
+    for f in $files; do echo "0.$i 10 100 $PWD/$f"; i=$((i + 1)); done
+    echo '#end{file description}'
+    echo '#begin{clone}'
+    i=0
+    for f in $files; do
+        if [ $i != $project ]; then
+            printf '#begin{set}\n0.%s 1,0,0 2,0,10 10\n0.%s 3,0,0 4,0,10 10\n#end{set}\n' $project $i
+        fi
+        i=$((i + 1))
+    done
+    echo '#begin{set}'
+    i=0
+    for f in $files; do echo "0.$i 5,0,20 6,0,30 10"; i=$((i + 1)); done
+    echo '#end{set}'
+    if [ $examples -gt 1 ]; then
+        echo '#begin{set}'
+        i=0
+        for f in $files; do
+            [ $i != $project ] && echo "0.$i 7,0,40 8,0,50 10"
+            i=$((i + 1))
+        done
+        echo '#end{set}'
+    fi
+    echo '#end{clone}'
+} > ../result.txt
+"#;
+
+    #[test]
+    fn test_batch_results_equal_single_results() {
+        let root = tempfile::tempdir().unwrap();
+        let executable_path = root.path().join("CCFinderSW");
+        fs::write(&executable_path, STUB_CCFINDERSW).unwrap();
+        fs::set_permissions(&executable_path, fs::Permissions::from_mode(0o755)).unwrap();
+        let project_path = root.path().join("project");
+        fs::create_dir(&project_path).unwrap();
+        fs::write(project_path.join("MyProject.ino"), "void setup() {}").unwrap();
+        let library_path = root.path().join("libraries/Library/1.0.0");
+        fs::create_dir_all(&library_path).unwrap();
+        let mut zip = ZipWriter::new(File::create(library_path.join("Library-1.0.0.zip")).unwrap());
+        for example in &["Example/Example.ino", "Other/Other.ino"] {
+            zip.start_file(
+                format!("Library-1.0.0/examples/{}", example),
+                FileOptions::default(),
+            )
+            .unwrap();
+            write!(zip, "void setup() {{}}").unwrap();
+        }
+        zip.finish().unwrap();
+
+        let config: Config = toml::from_str(&format!(
+            r#"
+munin_database_root = "~/munin"
+clone_detector_kind = "CCFinderSW"
+number_of_jobs = 1
+
+[clone_detector_config]
+executable_path = "{}"
+token_length = "50"
+language = "CPlusPlus"
+extensions = "ino"
+"#,
+            executable_path.to_str().unwrap()
+        ))
+        .unwrap();
+        let runner = CCFinderSWRunner::create(
+            CCFinderSWConfig::try_from_config(&config).unwrap(),
+            config.get_job_timeouts(),
+            &project_path,
+            Arc::new(ArchiveCache::new(root.path(), 1024)),
+            Arc::new(WorkDirs::new(None, KeepWorkDirs::Never).unwrap()),
+        );
+        let jobs = [test_job("Example/Example.ino"), test_job("Other/Other.ino")];
+
+        let batch: Vec<_> = runner
+            .run_batch(&jobs)
+            .into_iter()
+            .map(|o| o.unwrap())
+            .collect();
+        for (job, pairs) in jobs.iter().zip(batch) {
+            assert_eq!(pairs.len(), 2);
+            assert_eq!(runner.run_job(job.clone()).unwrap(), pairs);
+        }
+    }
 }
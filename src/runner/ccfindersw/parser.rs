@@ -1,7 +1,7 @@
 use std::error::Error;
 use std::path::Path;
 
-use log::{debug, error, info, warn};
+use log::{debug, error, info};
 
 use nom::branch::alt;
 use nom::bytes::complete::{tag, take_until};
@@ -108,6 +108,12 @@ impl ParsedResult {
         }
     }
 
+    /// Returns the clone pairs between the two files.
+    ///
+    /// The result may contain other files, e.g. when the jobs of a batch are checked at once. The
+    /// parts of the other files are ignored, so the pairs are the same as when the two files are
+    /// checked alone: the sets left with a single part were clones of the other files only and
+    /// are skipped, and the other sets must contain exactly one part of each file.
     pub fn get_clone_pairs(
         &self,
         project_file_name: &str,
@@ -122,12 +128,28 @@ impl ParsedResult {
             example_source_file_number
         );
         let mut res = Vec::new();
+        // NOTE: The skipped sets are counted to be logged.
+        let mut skipped_sets = 0;
         for s in &self.clone {
+            let elements: Vec<&SetElement> = s
+                .elements
+                .iter()
+                .filter(|e| {
+                    e.file_number == project_file_number
+                        || e.file_number == example_source_file_number
+                })
+                .collect();
+            if elements.len() < 2 {
+                if !elements.is_empty() {
+                    skipped_sets += 1;
+                }
+                continue;
+            }
             let mut project_code_part = None;
             let mut project_code_lnr = None;
             let mut example_code_part = None;
             let mut example_code_part_lnr = None;
-            for e in &s.elements {
+            for e in elements {
                 debug!("SetElement: {:?}", e);
                 project_code_part = if e.file_number == project_file_number {
                     debug!("Reached project_code_part set.");
@@ -178,6 +200,12 @@ impl ParsedResult {
             }?;
             res.push(new_pair);
         }
+        if skipped_sets > 0 {
+            info!(
+                "Skipped {} clone set(s) with a single part of {} and {}.",
+                skipped_sets, project_file_name, example_source_name
+            );
+        }
         Ok(res)
     }
 }
@@ -779,4 +807,115 @@ mod test {
         );
         assert_eq!(res, expected);
     }
+
+    fn parse(result: &str) -> ParsedResult {
+        let parser = ResultParser::new();
+        let (_, res) = parser
+            .parse_result::<nom::error::VerboseError<&str>>(result)
+            .unwrap();
+        res
+    }
+
+    #[test]
+    fn test_get_clone_pairs_in_batch() {
+        // The result of the job on MyProject.ino and Example.ino alone.
+        let single = "#begin{file description}
+0.0     100     444     /tmp/.foo/src/MyProject.ino
+0.1     250     1202    /tmp/.foo/src/Example.ino
+#end{file description}
+#begin{clone}
+#begin{set}
+0.0     130,40,656      141,4,692       81
+0.1     1,0,0   10,2,40 81
+#end{set}
+#end{clone}
+";
+        // The result of the same job in a batch with e1_Example.ino.
+        let batch = "#begin{file description}
+0.0     100     444     /tmp/.foo/src/p0_MyProject.ino
+0.1     250     1202    /tmp/.foo/src/e0_Example.ino
+0.2     250     1202    /tmp/.foo/src/e1_Example.ino
+#end{file description}
+#begin{clone}
+#begin{set}
+0.1     20,40,150       30,0,189        81
+0.2     20,40,150       30,0,189        81
+#end{set}
+#begin{set}
+0.0     130,40,656      141,4,692       81
+0.1     1,0,0   10,2,40 81
+0.2     1,0,0   10,2,40 81
+#end{set}
+#end{clone}
+";
+        let expected = vec![ClonePair::new(
+            CodeSlice::new(CodePosition::new(130, 40), CodePosition::new(141, 4)),
+            81.0,
+            CodeSlice::new(CodePosition::new(1, 0), CodePosition::new(10, 2)),
+            81.0,
+        )];
+        assert_eq!(
+            parse(single)
+                .get_clone_pairs("MyProject.ino", "Example.ino")
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse(batch)
+                .get_clone_pairs("p0_MyProject.ino", "e0_Example.ino")
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse(batch)
+                .get_clone_pairs("p0_MyProject.ino", "e1_Example.ino")
+                .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_get_clone_pairs_with_duplicated_parts() {
+        // A set with two parts of the example is invalid whether the job is checked alone or in a
+        // batch.
+        let single = "#begin{file description}
+0.0     100     444     /tmp/.foo/src/MyProject.ino
+0.1     250     1202    /tmp/.foo/src/Example.ino
+#end{file description}
+#begin{clone}
+#begin{set}
+0.0     130,40,656      141,4,692       81
+0.1     1,0,0   10,2,40 81
+0.1     20,40,150       30,0,189        81
+#end{set}
+#end{clone}
+";
+        let batch = "#begin{file description}
+0.0     100     444     /tmp/.foo/src/p0_MyProject.ino
+0.1     250     1202    /tmp/.foo/src/e0_Example.ino
+0.2     250     1202    /tmp/.foo/src/e1_Example.ino
+#end{file description}
+#begin{clone}
+#begin{set}
+0.0     130,40,656      141,4,692       81
+0.1     1,0,0   10,2,40 81
+0.1     20,40,150       30,0,189        81
+0.2     1,0,0   10,2,40 81
+#end{set}
+#end{clone}
+";
+        assert!(parse(single)
+            .get_clone_pairs("MyProject.ino", "Example.ino")
+            .is_err());
+        assert!(parse(batch)
+            .get_clone_pairs("p0_MyProject.ino", "e0_Example.ino")
+            .is_err());
+        assert_eq!(
+            parse(batch)
+                .get_clone_pairs("p0_MyProject.ino", "e1_Example.ino")
+                .unwrap()
+                .len(),
+            1
+        );
+    }
 }
@@ -0,0 +1,160 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::{debug, info};
+
+use crate::config::ccfindersw::WorkerConfig;
+use crate::error::{RunnerProcessFailedError, WorkerFailedError};
+use crate::runner::process::{capture, kill_process_group, CapturedOutput, Watchdog};
+
+/// A JVM running `tools/ccfindersw-worker/HuginWorker.java`.
+struct Worker {
+    child: Child,
+    requests: ChildStdin,
+    responses: BufReader<ChildStdout>,
+    stderr: Option<JoinHandle<(String, bool)>>,
+    exited: bool,
+}
+
+impl Worker {
+    fn spawn(config: &WorkerConfig) -> Result<Self, Box<dyn Error>> {
+        info!("Starting a CCFinderSW worker...");
+        let mut child = Command::new(config.get_java_path())
+            .args(config.to_java_arguments())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()?;
+        let requests = child.stdin.take().unwrap();
+        let responses = BufReader::new(child.stdout.take().unwrap());
+        let stderr = Some(capture(child.stderr.take().unwrap()));
+        let mut worker = Worker {
+            child,
+            requests,
+            responses,
+            stderr,
+            exited: false,
+        };
+        let response = worker.read_response()?;
+        if response != "READY" {
+            return Err(WorkerFailedError::new(
+                format!("Unexpected greeting: {}", response).as_str(),
+            )
+            .into());
+        }
+        Ok(worker)
+    }
+
+    /// Kills the worker and returns the end of its `stderr`.
+    fn collect_output(&mut self) -> CapturedOutput {
+        // NOTE: The worker may have left its children holding `stderr` open.
+        kill_process_group(self.child.id());
+        self.exited = true;
+        let _ = self.child.wait();
+        let (stderr, truncated) = self
+            .stderr
+            .take()
+            .map(|h| h.join().unwrap_or_default())
+            .unwrap_or_default();
+        CapturedOutput {
+            stdout: String::new(),
+            stderr,
+            truncated,
+        }
+    }
+
+    fn read_response(&mut self) -> Result<String, Box<dyn Error>> {
+        let mut line = String::new();
+        if self.responses.read_line(&mut line)? == 0 {
+            let output = self.collect_output();
+            let status_code = self.child.wait().ok().and_then(|s| s.code()).unwrap_or(-1);
+            return Err(RunnerProcessFailedError::new(status_code)
+                .with_output(output)
+                .into());
+        }
+        Ok(String::from(line.trim_end()))
+    }
+
+    fn request(&mut self, args: &[String]) -> Result<String, Box<dyn Error>> {
+        writeln!(self.requests, "{}", args.join("\t"))?;
+        self.requests.flush()?;
+        self.read_response()
+    }
+
+    /// Sends the request and waits for the response.
+    ///
+    /// A request exceeding the time limit (or cancelled) kills the worker, which makes the
+    /// pending read fail.
+    fn run(&mut self, args: &[String], timeout: Option<Duration>) -> Result<(), Box<dyn Error>> {
+        if args.iter().any(|a| a.contains('\t') || a.contains('\n')) {
+            return Err(
+                WorkerFailedError::new("The arguments can't be sent to the worker.").into(),
+            );
+        }
+        let watchdog = Watchdog::start(self.child.id(), timeout);
+        let response = self.request(args);
+        if let Some(reason) = watchdog.stop() {
+            // The killed worker closed its output, so the failed read already collected it.
+            let output = match response {
+                Err(e) => e
+                    .downcast_ref::<RunnerProcessFailedError>()
+                    .and_then(|e| e.get_output())
+                    .cloned(),
+                Ok(_) => None,
+            };
+            let output = output.unwrap_or_else(|| self.collect_output());
+            return Err(reason.into_error_with_output(output));
+        }
+        let response = response?;
+        if response == "DONE" {
+            Ok(())
+        } else {
+            Err(WorkerFailedError::new(response.as_str()).into())
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        if !self.exited {
+            kill_process_group(self.child.id());
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// Keeps the idle workers so that each thread reuses a warm JVM.
+pub struct WorkerPool {
+    config: WorkerConfig,
+    idle: Mutex<Vec<Worker>>,
+}
+
+impl WorkerPool {
+    pub fn new(config: WorkerConfig) -> Self {
+        WorkerPool {
+            config,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Runs CCFinderSW with the arguments on an idle worker (or a new one).
+    ///
+    /// A worker which failed or timed out is discarded.
+    pub fn run(&self, args: &[String], timeout: Option<Duration>) -> Result<(), Box<dyn Error>> {
+        let idle_worker = self.idle.lock().unwrap().pop();
+        let mut worker = match idle_worker {
+            Some(w) => w,
+            None => Worker::spawn(&self.config)?,
+        };
+        debug!("Sending a request to the worker: {:?}", args);
+        worker.run(args, timeout)?;
+        self.idle.lock().unwrap().push(worker);
+        Ok(())
+    }
+}
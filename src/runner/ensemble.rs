@@ -0,0 +1,331 @@
+use std::error::Error;
+
+use log::{debug, error};
+
+use crate::clone_pair::{ClonePair, Scores};
+use crate::config::ensemble::{EnsembleConfig, Quorum};
+use crate::job::Job;
+use crate::runner::{JobOutcome, Runner};
+
+/// The clone pairs reported by the named detector.
+type DetectorResult = (String, Vec<ClonePair>);
+
+/// The results of the members which succeeded on a job and the errors of the others.
+type MemberOutcomes = (Vec<DetectorResult>, Vec<Box<dyn Error>>);
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut i = i;
+    while parents[i] != root {
+        let next = parents[i];
+        parents[i] = root;
+        i = next;
+    }
+    root
+}
+
+/// Groups the clone pairs of different detectors whose project parts and example sketch parts
+/// both overlap, and keeps the groups reported by at least `required_votes` detectors.
+///
+/// The overlapping pairs of a single detector are not joined, so that the chained pairs of one
+/// detector don't make a group wider than what the others found. Each kept group is merged into
+/// a clone pair covering all of its parts. Its scores are the percentage of the
+/// `number_of_detectors` which reported it, and the scores of each detector are kept apart since
+/// they mean different things.
+fn vote(
+    results: &[DetectorResult],
+    number_of_detectors: usize,
+    required_votes: usize,
+) -> Vec<ClonePair> {
+    let pairs: Vec<(usize, &ClonePair)> = results
+        .iter()
+        .enumerate()
+        .flat_map(|(d, (_, pairs))| pairs.iter().map(move |p| (d, p)))
+        .collect();
+    let mut parents: Vec<usize> = (0..pairs.len()).collect();
+    for i in 0..pairs.len() {
+        for j in (i + 1)..pairs.len() {
+            let (detector_a, a) = pairs[i];
+            let (detector_b, b) = pairs[j];
+            if detector_a != detector_b
+                && a.get_project_part().overlaps(b.get_project_part())
+                && a.get_example_sketch_part()
+                    .overlaps(b.get_example_sketch_part())
+            {
+                let root_i = find_root(&mut parents, i);
+                let root_j = find_root(&mut parents, j);
+                parents[root_j] = root_i;
+            }
+        }
+    }
+
+    let mut res = Vec::new();
+    for root in 0..pairs.len() {
+        if find_root(&mut parents, root) != root {
+            continue;
+        }
+        let members: Vec<(usize, &ClonePair)> = (0..pairs.len())
+            .filter(|i| find_root(&mut parents, *i) == root)
+            .map(|i| pairs[i])
+            .collect();
+        let mut detectors: Vec<usize> = members.iter().map(|(d, _)| *d).collect();
+        detectors.sort_unstable();
+        detectors.dedup();
+        if detectors.len() < required_votes {
+            continue;
+        }
+
+        let (_, first) = members[0];
+        let mut project = first.get_project_part().clone();
+        let mut example_sketch = first.get_example_sketch_part().clone();
+        for (_, p) in &members[1..] {
+            project = project.union(p.get_project_part());
+            example_sketch = example_sketch.union(p.get_example_sketch_part());
+        }
+        let detector_scores = detectors
+            .iter()
+            .map(|d| {
+                let (project_score, example_sketch_score) = members
+                    .iter()
+                    .filter(|(m, _)| m == d)
+                    .map(|(_, p)| p.get_scores())
+                    .fold((0.0f64, 0.0f64), |(a, b), s| {
+                        (
+                            a.max(s.get_project_part()),
+                            b.max(s.get_example_sketch_part()),
+                        )
+                    });
+                (
+                    results[*d].0.clone(),
+                    Scores::new(project_score, example_sketch_score),
+                )
+            })
+            .collect();
+        let agreement = 100.0 * detectors.len() as f64 / number_of_detectors as f64;
+        res.push(
+            ClonePair::new(project, agreement, example_sketch, agreement)
+                .with_detectors(detector_scores),
+        );
+    }
+    res
+}
+
+pub struct EnsembleRunner {
+    members: Vec<(String, Box<dyn Runner + Sync + Send>)>,
+    quorum: Quorum,
+}
+
+impl EnsembleRunner {
+    pub fn create(
+        config: &EnsembleConfig,
+        members: Vec<(String, Box<dyn Runner + Sync + Send>)>,
+    ) -> Self {
+        EnsembleRunner {
+            members,
+            quorum: config.get_quorum().clone(),
+        }
+    }
+
+    /// Votes on the results of the members which succeeded.
+    ///
+    /// A failed member doesn't vote, so the pairs it would have reported may miss the quorum. The
+    /// job fails only if every member failed.
+    fn combine(&self, results: Vec<DetectorResult>, mut errors: Vec<Box<dyn Error>>) -> JobOutcome {
+        if results.is_empty() {
+            return Err(errors.remove(0));
+        }
+        let required_votes = self.quorum.get_required_votes(self.members.len());
+        let clone_pairs = vote(&results, self.members.len(), required_votes);
+        debug!("pairs: {:?}", clone_pairs);
+        Ok(clone_pairs)
+    }
+}
+
+impl Runner for EnsembleRunner {
+    fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let mut results = Vec::new();
+        let mut errors = Vec::new();
+        for (name, runner) in &self.members {
+            debug!("Running the member detector: {}", name);
+            match runner.run_job(job.clone()) {
+                Ok(pairs) => results.push((name.clone(), pairs)),
+                Err(e) => {
+                    error!(
+                        "The member detector `{}` failed and doesn't vote: {}",
+                        name, e
+                    );
+                    errors.push(e);
+                }
+            }
+        }
+        self.combine(results, errors)
+    }
+
+    fn run_batch(&self, jobs: &[Job]) -> Vec<JobOutcome> {
+        let mut results: Vec<MemberOutcomes> =
+            jobs.iter().map(|_| (Vec::new(), Vec::new())).collect();
+        for (name, runner) in &self.members {
+            debug!("Running the member detector on a batch: {}", name);
+            for ((job_results, job_errors), member_result) in
+                results.iter_mut().zip(runner.run_batch(jobs))
+            {
+                match member_result {
+                    Ok(pairs) => job_results.push((name.clone(), pairs)),
+                    Err(e) => {
+                        error!(
+                            "The member detector `{}` failed and doesn't vote: {}",
+                            name, e
+                        );
+                        job_errors.push(e);
+                    }
+                }
+            }
+        }
+        results
+            .into_iter()
+            .map(|(r, e)| self.combine(r, e))
+            .collect()
+    }
+
+    fn get_command_lines(&self, jobs: &[Job]) -> Vec<String> {
+        self.members
+            .iter()
+            .flat_map(|(name, runner)| {
+                runner
+                    .get_command_lines(jobs)
+                    .into_iter()
+                    .map(move |c| format!("[{}] {}", name, c))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use crate::clone_pair::{ClonePair, CodePosition, CodeSlice, Scores};
+    use crate::config::ensemble::Quorum;
+    use crate::error::RunnerProcessFailedError;
+    use crate::job::{test_job, Job};
+    use crate::runner::ensemble::{vote, DetectorResult, EnsembleRunner};
+    use crate::runner::Runner;
+
+    fn pair(project: (u32, u32), example_sketch: (u32, u32), score: f64) -> ClonePair {
+        ClonePair::new(
+            CodeSlice::new(
+                CodePosition::new(project.0, 0),
+                CodePosition::new(project.1, 0),
+            ),
+            score,
+            CodeSlice::new(
+                CodePosition::new(example_sketch.0, 0),
+                CodePosition::new(example_sketch.1, 0),
+            ),
+            score,
+        )
+    }
+
+    fn detector_scores(scores: &[(&str, f64)]) -> Vec<(String, Scores)> {
+        scores
+            .iter()
+            .map(|(d, s)| (String::from(*d), Scores::new(*s, *s)))
+            .collect()
+    }
+
+    fn results() -> Vec<DetectorResult> {
+        vec![
+            (
+                String::from("CCFinderSW"),
+                vec![
+                    pair((10, 20), (30, 40), 50.0),
+                    pair((100, 110), (1, 5), 60.0),
+                ],
+            ),
+            (String::from("Native"), vec![pair((15, 25), (35, 45), 70.0)]),
+            (String::from("NiCad"), vec![pair((12, 18), (50, 60), 80.0)]),
+        ]
+    }
+
+    #[test]
+    fn test_vote_majority() {
+        let res = vote(&results(), 3, 2);
+        assert_eq!(
+            res,
+            vec![pair((10, 25), (30, 45), 200.0 / 3.0)
+                .with_detectors(detector_scores(&[("CCFinderSW", 50.0), ("Native", 70.0)]))]
+        );
+        // The scores of the detectors are kept in the results.
+        let serialized = toml::to_string(&res[0]).unwrap();
+        assert_eq!(toml::from_str::<ClonePair>(&serialized).unwrap(), res[0]);
+    }
+
+    #[test]
+    fn test_vote_any_and_all() {
+        assert_eq!(vote(&results(), 3, 1).len(), 3);
+        assert!(vote(&results(), 3, 3).is_empty());
+    }
+
+    #[test]
+    fn test_vote_single_detector() {
+        // The overlapping pairs of one detector are not chained into a wider pair.
+        let results = vec![(
+            String::from("CCFinderSW"),
+            vec![
+                pair((10, 20), (30, 40), 50.0),
+                pair((18, 30), (38, 50), 50.0),
+            ],
+        )];
+        assert_eq!(vote(&results, 2, 1).len(), 2);
+    }
+
+    /// Reports a clone pair, or fails if it is broken.
+    struct StubRunner {
+        broken: bool,
+    }
+
+    impl Runner for StubRunner {
+        fn run_job(&self, _job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+            if self.broken {
+                Err(Box::new(RunnerProcessFailedError::new(1)))
+            } else {
+                Ok(vec![pair((10, 20), (30, 40), 50.0)])
+            }
+        }
+    }
+
+    fn create_runner(broken: &[bool]) -> EnsembleRunner {
+        EnsembleRunner {
+            members: broken
+                .iter()
+                .enumerate()
+                .map(|(i, b)| -> (String, Box<dyn Runner + Sync + Send>) {
+                    (format!("Member{}", i), Box::new(StubRunner { broken: *b }))
+                })
+                .collect(),
+            quorum: Quorum::Any,
+        }
+    }
+
+    #[test]
+    fn test_failed_member() {
+        // A failed member doesn't vote, and the job fails only if every member failed.
+        let job = test_job("Example/Example.ino");
+        let pairs = create_runner(&[false, true]).run_job(job.clone()).unwrap();
+        assert_eq!(
+            pairs,
+            vec![pair((10, 20), (30, 40), 50.0)
+                .with_detectors(detector_scores(&[("Member0", 50.0)]))]
+        );
+        assert_eq!(
+            create_runner(&[false, true]).run_batch(std::slice::from_ref(&job))[0]
+                .as_ref()
+                .unwrap(),
+            &pairs
+        );
+        assert!(create_runner(&[true, true]).run_job(job).is_err());
+    }
+}
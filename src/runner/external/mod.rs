@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use log::debug;
+
+mod output;
+
+use crate::clone_pair::ClonePair;
+use crate::config::external::{ExternalConfig, Layout};
+use crate::config::JobTimeouts;
+use crate::error::RunnerProcessFailedError;
+use crate::job::Job;
+use crate::runner::archive::ArchiveCache;
+use crate::runner::external::output::parse_output;
+use crate::runner::process::run_with_timeout;
+use crate::runner::workdir::{format_command, record_command, WorkDirs, WORKING_DIR_PLACEHOLDER};
+use crate::runner::{stage_sources, Runner};
+
+/// Returns the name of the staged file, which the CCFX output gives without its directories.
+fn get_file_name(staged_source: &str) -> &str {
+    Path::new(staged_source)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(staged_source)
+}
+
+#[derive(Clone)]
+pub struct ExternalRunner {
+    project_path: PathBuf,
+    archives: Arc<ArchiveCache>,
+    config: ExternalConfig,
+    timeouts: JobTimeouts,
+    work_dirs: Arc<WorkDirs>,
+}
+
+impl ExternalRunner {
+    pub fn create(
+        config: ExternalConfig,
+        timeouts: JobTimeouts,
+        project_path: &Path,
+        archives: Arc<ArchiveCache>,
+        work_dirs: Arc<WorkDirs>,
+    ) -> Self {
+        ExternalRunner {
+            project_path: PathBuf::from(project_path),
+            archives,
+            config,
+            timeouts,
+            work_dirs,
+        }
+    }
+
+    /// Returns the layout variables of the job and the layout they give.
+    fn get_layout(&self, job: &Job) -> Result<(HashMap<String, String>, Layout), Box<dyn Error>> {
+        let variables: HashMap<String, String> = [
+            ("project_name", job.get_project().get_file_name()?),
+            ("example_name", job.get_example_sketch().get_file_name()?),
+            (
+                "library_name",
+                String::from(job.get_library_info().get_name()),
+            ),
+        ]
+        .iter()
+        .map(|(k, v)| (String::from(*k), v.clone()))
+        .collect();
+        let layout = self.config.expand_layout(&variables)?;
+        Ok((variables, layout))
+    }
+
+    fn create_command(&self, job: &Job, working_dir: &Path) -> Result<Command, Box<dyn Error>> {
+        let (mut variables, layout) = self.get_layout(job)?;
+        let sources_path = working_dir.join(&layout.sources_dir);
+        let job_variables: Vec<(&str, PathBuf)> = vec![
+            ("working_dir", working_dir.to_path_buf()),
+            ("sources_dir", sources_path.clone()),
+            ("project_file", sources_path.join(&layout.project_source)),
+            ("example_file", sources_path.join(&layout.example_source)),
+            ("output_file", working_dir.join(&layout.output_file)),
+        ];
+        variables.extend(
+            job_variables
+                .iter()
+                .map(|(k, v)| (String::from(*k), String::from(v.to_str().unwrap()))),
+        );
+        let args = self.config.expand_command(&variables)?;
+        let mut command = Command::new(&args[0]);
+        command.current_dir(working_dir).args(&args[1..]);
+        Ok(command)
+    }
+
+    fn run_job_in(&self, job: &Job, working_dir: &Path) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let (_, layout) = self.get_layout(job)?;
+        let sources_path = working_dir.join(&layout.sources_dir);
+        for source in &[&layout.project_source, &layout.example_source] {
+            if let Some(parent) = sources_path.join(source).parent() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let output_path = working_dir.join(&layout.output_file);
+
+        stage_sources(
+            job,
+            &self.project_path,
+            &self.archives,
+            &sources_path,
+            &layout.project_source,
+            &layout.example_source,
+        )?;
+
+        let mut command = self.create_command(job, working_dir)?;
+        debug!("Running the detector: {:?}", command);
+        record_command(working_dir, &command)?;
+        let res = run_with_timeout(
+            &mut command,
+            self.timeouts.get_timeout(job.get_library_info()),
+        )?;
+        if res.status.success() {
+            let mut file = File::open(&output_path)?;
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            let clone_pairs = parse_output(
+                self.config.get_output_format(),
+                &contents,
+                get_file_name(&layout.project_source),
+                get_file_name(&layout.example_source),
+            )?;
+            debug!("pairs: {:?}", clone_pairs);
+            Ok(clone_pairs)
+        } else {
+            Err(
+                RunnerProcessFailedError::new(res.status.code().unwrap_or(-1))
+                    .with_output(res.output)
+                    .into(),
+            )
+        }
+    }
+}
+
+impl Runner for ExternalRunner {
+    fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let working_dir = self.work_dirs.create()?;
+        let res = self.run_job_in(&job, working_dir.path());
+        self.work_dirs
+            .finish(working_dir, std::slice::from_ref(&job), res.is_ok());
+        res
+    }
+
+    fn get_command_lines(&self, jobs: &[Job]) -> Vec<String> {
+        jobs.iter()
+            .filter_map(|j| {
+                self.create_command(j, Path::new(WORKING_DIR_PLACEHOLDER))
+                    .ok()
+            })
+            .map(|c| format_command(&c))
+            .collect()
+    }
+}
@@ -0,0 +1,137 @@
+use std::error::Error;
+
+use crate::clone_pair::{ClonePair, CodePosition, CodeSlice};
+use crate::config::external::OutputFormat;
+use crate::error::InvalidDetectorOutput;
+use crate::runner::ccfindersw::parser::ResultParser;
+
+/**
+= CSV output format
+
+One clone pair per line. Empty lines, lines starting with `#` and a header before the first pair
+are ignored.
+
+project_start_line,project_start_column,project_end_line,project_end_column,
+example_start_line,example_start_column,example_end_line,example_end_column
+[,project_score,example_score]
+ */
+fn parse_csv(contents: &str) -> Result<Vec<ClonePair>, InvalidDetectorOutput> {
+    let mut res = Vec::new();
+    let mut header_allowed = true;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        // NOTE: Only the first line other than the comments may be the header.
+        let is_header = header_allowed && fields[0].parse::<u32>().is_err();
+        header_allowed = false;
+        if is_header {
+            continue;
+        }
+        if fields.len() != 8 && fields.len() != 10 {
+            return Err(InvalidDetectorOutput::new(
+                format!("Wrong number of fields at line {}.", i + 1).as_str(),
+            ));
+        }
+        let positions = fields[..8]
+            .iter()
+            .map(|f| f.parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|_| {
+                InvalidDetectorOutput::new(format!("Invalid position at line {}.", i + 1).as_str())
+            })?;
+        let scores = fields[8..]
+            .iter()
+            .map(|f| f.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| {
+                InvalidDetectorOutput::new(format!("Invalid score at line {}.", i + 1).as_str())
+            })?;
+        let (project_score, example_score) = if scores.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (scores[0], scores[1])
+        };
+        res.push(ClonePair::new(
+            CodeSlice::new(
+                CodePosition::new(positions[0], positions[1]),
+                CodePosition::new(positions[2], positions[3]),
+            ),
+            project_score,
+            CodeSlice::new(
+                CodePosition::new(positions[4], positions[5]),
+                CodePosition::new(positions[6], positions[7]),
+            ),
+            example_score,
+        ));
+    }
+    Ok(res)
+}
+
+/// Reads the clone pairs between the two staged files from the output of the detector.
+///
+/// The JSON format is an array of clone pairs in the same form as the Hugin result.
+pub fn parse_output(
+    format: &OutputFormat,
+    contents: &str,
+    project_source_name: &str,
+    example_source_name: &str,
+) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+    match format {
+        OutputFormat::Ccfx => {
+            let parser = ResultParser::new();
+            let (_, parse_result) = parser.parse_result::<()>(contents)?;
+            parse_result.get_clone_pairs(project_source_name, example_source_name)
+        }
+        OutputFormat::Json => Ok(serde_json::from_str(contents)?),
+        OutputFormat::Csv => Ok(parse_csv(contents)?),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clone_pair::{ClonePair, CodePosition, CodeSlice};
+    use crate::config::external::OutputFormat;
+    use crate::runner::external::output::{parse_csv, parse_output};
+
+    fn expected() -> ClonePair {
+        ClonePair::new(
+            CodeSlice::new(CodePosition::new(130, 40), CodePosition::new(141, 4)),
+            81.0,
+            CodeSlice::new(CodePosition::new(20, 40), CodePosition::new(30, 0)),
+            81.0,
+        )
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let contents = "project_start_line,project_start_column,project_end_line,project_end_column,example_start_line,example_start_column,example_end_line,example_end_column,project_score,example_score
+# comment
+130,40,141,4,20,40,30,0,81,81
+";
+        assert_eq!(parse_csv(contents).unwrap(), vec![expected()]);
+        assert!(parse_csv("1,2,3\n").is_err());
+
+        let contents = "# generated by my-detector
+
+project_start_line,project_start_column,project_end_line,project_end_column,example_start_line,example_start_column,example_end_line,example_end_column
+130,40,141,4,20,40,30,0,81,81
+";
+        assert_eq!(parse_csv(contents).unwrap(), vec![expected()]);
+        // A header after the first pair is an error.
+        assert!(parse_csv("130,40,141,4,20,40,30,0\nproject_start_line\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let contents = r#"[{
+  "project": {"start": {"lines": 130, "columns": 40}, "end": {"lines": 141, "columns": 4}},
+  "example_sketch": {"start": {"lines": 20, "columns": 40}, "end": {"lines": 30, "columns": 0}},
+  "scores": {"project_part": 81.0, "example_sketch_part": 81.0}
+}]"#;
+        let res = parse_output(&OutputFormat::Json, contents, "", "").unwrap();
+        assert_eq!(res, vec![expected()]);
+    }
+}
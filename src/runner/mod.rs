@@ -1,10 +1,241 @@
 use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use log::{debug, error, info};
 
 use crate::clone_pair::ClonePair;
+use crate::config::ccfindersw::CCFinderSWConfig;
+use crate::config::ensemble::EnsembleConfig;
+use crate::config::external::ExternalConfig;
+use crate::config::native::NativeConfig;
+use crate::config::nicad::NiCadConfig;
+use crate::config::plugin::PluginConfig;
+use crate::config::{CloneDetectorKind, Config};
+use crate::error::NoValidConfigurationError;
 use crate::job::Job;
+use crate::runner::archive::ArchiveCache;
+use crate::runner::ccfindersw::CCFinderSWRunner;
+use crate::runner::ensemble::EnsembleRunner;
+use crate::runner::external::ExternalRunner;
+use crate::runner::native::NativeRunner;
+use crate::runner::nicad::NiCadRunner;
+use crate::runner::plugin::PluginRunner;
+use crate::runner::workdir::WorkDirs;
 
+pub mod archive;
+pub mod batch;
+pub mod caching;
 pub mod ccfindersw;
+pub mod ensemble;
+pub mod external;
+pub mod native;
+pub mod nicad;
+pub mod plugin;
+pub mod process;
+pub mod workdir;
+
+/// The clone pairs found by a job or the reason why the job failed.
+pub type JobOutcome = Result<Vec<ClonePair>, Box<dyn Error>>;
 
 pub trait Runner {
     fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>>;
+
+    /// Runs the jobs grouped by `batch::group_jobs` and returns the results in the same order.
+    ///
+    /// Runners which can process many files at once override this to share a single detector
+    /// invocation among the jobs.
+    fn run_batch(&self, jobs: &[Job]) -> Vec<JobOutcome> {
+        jobs.iter().map(|j| self.run_job(j.clone())).collect()
+    }
+
+    /// Returns the command lines which `run_batch` would run for the jobs, with
+    /// `workdir::WORKING_DIR_PLACEHOLDER` in place of the working directory.
+    ///
+    /// The detectors running in process have no command lines.
+    fn get_command_lines(&self, _jobs: &[Job]) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+pub fn create_runner(
+    config: &Config,
+    project_path: &Path,
+    archives: &Arc<ArchiveCache>,
+    work_dirs: &Arc<WorkDirs>,
+) -> Result<Box<dyn Runner + Sync + Send>, Box<dyn Error>> {
+    match config.get_clone_detector_kind() {
+        CloneDetectorKind::CCFinderSW => {
+            let ccfindersw_config = CCFinderSWConfig::try_from_config(config).ok_or_else(|| {
+                error!("No valid configuration.");
+                NoValidConfigurationError
+            })?;
+            info!("CCFinderSW configuration: {:?}", ccfindersw_config);
+            Ok(Box::new(CCFinderSWRunner::create(
+                ccfindersw_config,
+                config.get_job_timeouts(),
+                project_path,
+                archives.clone(),
+                work_dirs.clone(),
+            )))
+        }
+        CloneDetectorKind::Native => {
+            let native_config = NativeConfig::try_from_config(config).ok_or_else(|| {
+                error!("No valid configuration.");
+                NoValidConfigurationError
+            })?;
+            info!("Native detector configuration: {:?}", native_config);
+            Ok(Box::new(NativeRunner::create(
+                native_config,
+                project_path,
+                archives.clone(),
+            )))
+        }
+        CloneDetectorKind::NiCad => {
+            let nicad_config = NiCadConfig::try_from_config(config).ok_or_else(|| {
+                error!("No valid configuration.");
+                NoValidConfigurationError
+            })?;
+            info!("NiCad configuration: {:?}", nicad_config);
+            Ok(Box::new(NiCadRunner::create(
+                nicad_config,
+                config.get_job_timeouts(),
+                project_path,
+                archives.clone(),
+                work_dirs.clone(),
+            )))
+        }
+        CloneDetectorKind::External => {
+            let external_config = ExternalConfig::try_from_config(config).ok_or_else(|| {
+                error!("No valid configuration.");
+                NoValidConfigurationError
+            })?;
+            info!("External detector configuration: {:?}", external_config);
+            Ok(Box::new(ExternalRunner::create(
+                external_config,
+                config.get_job_timeouts(),
+                project_path,
+                archives.clone(),
+                work_dirs.clone(),
+            )))
+        }
+        CloneDetectorKind::Ensemble => {
+            let ensemble_config = EnsembleConfig::try_from_config(config).ok_or_else(|| {
+                error!("No valid configuration.");
+                NoValidConfigurationError
+            })?;
+            let mut members = Vec::new();
+            for (name, member_config) in ensemble_config.get_members() {
+                members.push((
+                    name.clone(),
+                    create_runner(member_config, project_path, archives, work_dirs)?,
+                ));
+            }
+            info!("Ensemble quorum: {:?}", ensemble_config.get_quorum());
+            Ok(Box::new(EnsembleRunner::create(&ensemble_config, members)))
+        }
+        CloneDetectorKind::Plugin => {
+            let plugin_config = PluginConfig::try_from_config(config).ok_or_else(|| {
+                error!("No valid configuration.");
+                NoValidConfigurationError
+            })?;
+            info!("Plugin configuration: {:?}", plugin_config);
+            Ok(Box::new(PluginRunner::create(
+                plugin_config,
+                config.get_job_timeouts(),
+                project_path,
+                archives.clone(),
+                work_dirs.clone(),
+            )))
+        }
+    }
+}
+
+/// Returns the programs run by the detector so that they can be checked before a run.
+pub fn get_detector_programs(config: &Config) -> Result<Vec<String>, Box<dyn Error>> {
+    match config.get_clone_detector_kind() {
+        CloneDetectorKind::CCFinderSW => {
+            let ccfindersw_config =
+                CCFinderSWConfig::try_from_config(config).ok_or(NoValidConfigurationError)?;
+            let mut programs = vec![ccfindersw_config.get_executable_path_as_string()];
+            if let Some(worker_config) = ccfindersw_config.get_worker_config() {
+                programs.push(String::from(worker_config.get_java_path()));
+            }
+            Ok(programs)
+        }
+        CloneDetectorKind::Native => {
+            NativeConfig::try_from_config(config).ok_or(NoValidConfigurationError)?;
+            Ok(Vec::new())
+        }
+        CloneDetectorKind::NiCad => {
+            let nicad_config =
+                NiCadConfig::try_from_config(config).ok_or(NoValidConfigurationError)?;
+            Ok(vec![nicad_config.get_executable_path_as_string()])
+        }
+        CloneDetectorKind::External => {
+            let external_config =
+                ExternalConfig::try_from_config(config).ok_or(NoValidConfigurationError)?;
+            Ok(vec![external_config.get_program()])
+        }
+        CloneDetectorKind::Ensemble => {
+            let ensemble_config =
+                EnsembleConfig::try_from_config(config).ok_or(NoValidConfigurationError)?;
+            let mut programs = Vec::new();
+            for (_, member_config) in ensemble_config.get_members() {
+                programs.extend(get_detector_programs(member_config)?);
+            }
+            Ok(programs)
+        }
+        CloneDetectorKind::Plugin => {
+            let plugin_config =
+                PluginConfig::try_from_config(config).ok_or(NoValidConfigurationError)?;
+            Ok(vec![plugin_config.get_program()])
+        }
+    }
+}
+
+pub fn read_example_sketch(job: &Job, archives: &ArchiveCache) -> Result<String, Box<dyn Error>> {
+    archives.read_example_sketch(job)
+}
+
+pub fn stage_project_source(
+    job: &Job,
+    project_path: &Path,
+    sources_path: &Path,
+    project_source_name: &str,
+) -> Result<(), Box<dyn Error>> {
+    let project_source_path = job.get_project().get_location_from(project_path)?;
+    debug!(
+        "Copying the project source file...: {}",
+        project_source_path.to_str().unwrap()
+    );
+    fs::copy(project_source_path, sources_path.join(project_source_name))?;
+    Ok(())
+}
+
+pub fn stage_example_sketch(
+    job: &Job,
+    archives: &ArchiveCache,
+    sources_path: &Path,
+    example_source_name: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut example_source = File::create(sources_path.join(example_source_name))?;
+    let contents = read_example_sketch(job, archives)?;
+    write!(example_source, "{}", contents)?;
+    Ok(())
+}
+
+pub fn stage_sources(
+    job: &Job,
+    project_path: &Path,
+    archives: &ArchiveCache,
+    sources_path: &Path,
+    project_source_name: &str,
+    example_source_name: &str,
+) -> Result<(), Box<dyn Error>> {
+    stage_project_source(job, project_path, sources_path, project_source_name)?;
+    stage_example_sketch(job, archives, sources_path, example_source_name)
 }
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use crate::clone_pair::{ClonePair, CodeSlice};
+use crate::runner::native::tokenizer::Token;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Match {
+    project_offset: usize,
+    example_offset: usize,
+    length: usize,
+}
+
+fn find_matches(project: &[&str], example: &[&str], token_length: usize) -> Vec<Match> {
+    let mut res = Vec::new();
+    if token_length == 0 || project.len() < token_length || example.len() < token_length {
+        return res;
+    }
+
+    let mut windows: HashMap<&[&str], Vec<usize>> = HashMap::new();
+    for (j, w) in example.windows(token_length).enumerate() {
+        windows.entry(w).or_default().push(j);
+    }
+
+    for (i, w) in project.windows(token_length).enumerate() {
+        let candidates = match windows.get(w) {
+            Some(c) => c,
+            None => continue,
+        };
+        for &j in candidates {
+            // Only report maximal matches: skip the ones which extend an earlier match.
+            if i > 0 && j > 0 && project[i - 1] == example[j - 1] {
+                continue;
+            }
+            let mut length = token_length;
+            while i + length < project.len()
+                && j + length < example.len()
+                && project[i + length] == example[j + length]
+            {
+                length += 1;
+            }
+            res.push(Match {
+                project_offset: i,
+                example_offset: j,
+                length,
+            });
+        }
+    }
+    res
+}
+
+fn to_code_slice(tokens: &[Token], offset: usize, length: usize) -> CodeSlice {
+    CodeSlice::new(
+        tokens[offset].get_start().clone(),
+        tokens[offset + length - 1].get_end().clone(),
+    )
+}
+
+/// Finds the token sequences of at least `token_length` tokens shared by the two sources.
+///
+/// The score of each part is the length of the clone in tokens.
+pub fn detect_clones(
+    project: &[Token],
+    example: &[Token],
+    token_length: usize,
+    normalize: bool,
+) -> Vec<ClonePair> {
+    let project_texts: Vec<&str> = project
+        .iter()
+        .map(|t| t.get_comparison_text(normalize))
+        .collect();
+    let example_texts: Vec<&str> = example
+        .iter()
+        .map(|t| t.get_comparison_text(normalize))
+        .collect();
+    find_matches(&project_texts, &example_texts, token_length)
+        .iter()
+        .map(|m| {
+            ClonePair::new(
+                to_code_slice(project, m.project_offset, m.length),
+                m.length as f64,
+                to_code_slice(example, m.example_offset, m.length),
+                m.length as f64,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clone_pair::{ClonePair, CodePosition, CodeSlice};
+    use crate::runner::native::detector::{detect_clones, find_matches, Match};
+    use crate::runner::native::tokenizer::tokenize;
+
+    #[test]
+    fn test_find_matches_maximal() {
+        let project = ["a", "b", "c", "d", "e", "x"];
+        let example = ["y", "a", "b", "c", "d", "e"];
+        let res = find_matches(&project, &example, 3);
+        assert_eq!(
+            res,
+            vec![Match {
+                project_offset: 0,
+                example_offset: 1,
+                length: 5,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_matches_too_short() {
+        let project = ["a", "b"];
+        let example = ["a", "b"];
+        assert!(find_matches(&project, &example, 3).is_empty());
+    }
+
+    #[test]
+    fn test_detect_clones_normalized() {
+        let project = tokenize("void loop() {\n  int a = 1;\n}\n");
+        let example = tokenize("// renamed\nvoid loop() {\n  int b = 2;\n}\n");
+        let res = detect_clones(&project, &example, 5, true);
+        assert_eq!(
+            res,
+            vec![ClonePair::new(
+                CodeSlice::new(CodePosition::new(1, 0), CodePosition::new(3, 1)),
+                11.0,
+                CodeSlice::new(CodePosition::new(2, 0), CodePosition::new(4, 1)),
+                11.0,
+            )]
+        );
+        assert!(detect_clones(&project, &example, 7, false).is_empty());
+    }
+}
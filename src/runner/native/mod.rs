@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::debug;
+
+mod detector;
+mod tokenizer;
+
+use crate::clone_pair::ClonePair;
+use crate::config::native::NativeConfig;
+use crate::job::Job;
+use crate::runner::archive::ArchiveCache;
+use crate::runner::native::detector::detect_clones;
+use crate::runner::native::tokenizer::tokenize;
+use crate::runner::{read_example_sketch, Runner};
+
+#[derive(Clone)]
+pub struct NativeRunner {
+    project_path: PathBuf,
+    archives: Arc<ArchiveCache>,
+    config: NativeConfig,
+}
+
+impl NativeRunner {
+    pub fn create(config: NativeConfig, project_path: &Path, archives: Arc<ArchiveCache>) -> Self {
+        NativeRunner {
+            project_path: PathBuf::from(project_path),
+            archives,
+            config,
+        }
+    }
+}
+
+impl Runner for NativeRunner {
+    fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let project_source_path = job.get_project().get_location_from(&self.project_path)?;
+        debug!(
+            "Reading the project source file...: {}",
+            project_source_path.to_str().unwrap()
+        );
+        let project_source = String::from_utf8_lossy(&fs::read(project_source_path)?).into_owned();
+        let example_source = read_example_sketch(&job, &self.archives)?;
+
+        let project_tokens = tokenize(&project_source);
+        let example_tokens = tokenize(&example_source);
+        let clone_pairs = detect_clones(
+            &project_tokens,
+            &example_tokens,
+            self.config.get_token_length(),
+            self.config.is_normalization_enabled(),
+        );
+        debug!("pairs: {:?}", clone_pairs);
+        Ok(clone_pairs)
+    }
+}
@@ -0,0 +1,337 @@
+use crate::clone_pair::CodePosition;
+
+const KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "asm",
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "constexpr",
+    "const_cast",
+    "continue",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "dynamic_cast",
+    "else",
+    "enum",
+    "explicit",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "nullptr",
+    "operator",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "while",
+];
+
+// NOTE: Longer punctuators must come first so that the longest match wins.
+const PUNCTUATORS: &[&str] = &[
+    "<<=", ">>=", "->*", "...", "::", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&",
+    "||", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", ".*",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Keyword,
+    Identifier,
+    Literal,
+    Punctuator,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    kind: TokenKind,
+    text: String,
+    start: CodePosition,
+    end: CodePosition,
+}
+
+impl Token {
+    pub fn get_start(&self) -> &CodePosition {
+        &self.start
+    }
+
+    pub fn get_end(&self) -> &CodePosition {
+        &self.end
+    }
+
+    /// Returns the text used for comparing tokens.
+    ///
+    /// With normalization, identifiers and literals are replaced by placeholders so that
+    /// renamed copies (type-2 clones) are still detected as CCFinderSW does.
+    pub fn get_comparison_text(&self, normalize: bool) -> &str {
+        if normalize {
+            match self.kind {
+                TokenKind::Identifier => "$id",
+                TokenKind::Literal => "$lit",
+                _ => self.text.as_str(),
+            }
+        } else {
+            self.text.as_str()
+        }
+    }
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    offset: usize,
+    lines: u32,
+    columns: u32,
+}
+
+impl Cursor {
+    fn new(source: &str) -> Self {
+        Cursor {
+            chars: source.chars().collect(),
+            offset: 0,
+            lines: 1,
+            columns: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.chars.get(self.offset + n).cloned()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_nth(i) == Some(c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += 1;
+        if c == '\n' {
+            self.lines += 1;
+            self.columns = 0;
+        } else {
+            self.columns += 1;
+        }
+        Some(c)
+    }
+
+    fn position(&self) -> CodePosition {
+        CodePosition::new(self.lines, self.columns)
+    }
+
+    fn is_at_line_start(&self) -> bool {
+        self.chars[..self.offset]
+            .iter()
+            .rev()
+            .take_while(|c| **c != '\n')
+            .all(|c| c.is_whitespace())
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            if c == '\\' && self.peek_nth(1) == Some('\n') {
+                self.bump();
+            }
+            self.bump();
+        }
+    }
+
+    fn skip_block_comment(&mut self) {
+        self.bump();
+        self.bump();
+        while self.peek().is_some() && !self.starts_with("*/") {
+            self.bump();
+        }
+        self.bump();
+        self.bump();
+    }
+
+    fn take_quoted(&mut self, quote: char) -> String {
+        let mut text = String::new();
+        text.extend(self.bump());
+        while let Some(c) = self.bump() {
+            text.push(c);
+            if c == '\\' {
+                text.extend(self.bump());
+            } else if c == quote || c == '\n' {
+                break;
+            }
+        }
+        text
+    }
+
+    fn take_while<F>(&mut self, predicate: F) -> String
+    where
+        F: Fn(char) -> bool,
+    {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
+            }
+            text.extend(self.bump());
+        }
+        text
+    }
+}
+
+/// Splits a C/C++/Arduino source into tokens.
+///
+/// Comments, whitespace and preprocessor directives are dropped.
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut cursor = Cursor::new(source);
+    let mut tokens = Vec::new();
+    while let Some(c) = cursor.peek() {
+        if c.is_whitespace() {
+            cursor.bump();
+            continue;
+        }
+        if cursor.starts_with("//") {
+            cursor.skip_line();
+            continue;
+        }
+        if cursor.starts_with("/*") {
+            cursor.skip_block_comment();
+            continue;
+        }
+        if c == '#' && cursor.is_at_line_start() {
+            cursor.skip_line();
+            continue;
+        }
+
+        let start = cursor.position();
+        let (kind, text) = if c == '"' || c == '\'' {
+            (TokenKind::Literal, cursor.take_quoted(c))
+        } else if c.is_ascii_digit()
+            || (c == '.' && cursor.peek_nth(1).is_some_and(|n| n.is_ascii_digit()))
+        {
+            let text = cursor.take_while(|c| c.is_alphanumeric() || c == '.' || c == '_');
+            (TokenKind::Literal, text)
+        } else if c.is_alphabetic() || c == '_' {
+            let text = cursor.take_while(|c| c.is_alphanumeric() || c == '_');
+            if KEYWORDS.contains(&text.as_str()) {
+                (TokenKind::Keyword, text)
+            } else {
+                (TokenKind::Identifier, text)
+            }
+        } else {
+            let text = match PUNCTUATORS.iter().find(|p| cursor.starts_with(p)) {
+                Some(p) => {
+                    for _ in 0..p.len() {
+                        cursor.bump();
+                    }
+                    String::from(*p)
+                }
+                None => cursor.bump().into_iter().collect(),
+            };
+            (TokenKind::Punctuator, text)
+        };
+        tokens.push(Token {
+            kind,
+            text,
+            start,
+            end: cursor.position(),
+        });
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clone_pair::CodePosition;
+    use crate::runner::native::tokenizer::{tokenize, TokenKind};
+
+    #[test]
+    fn test_tokenize_kinds() {
+        let tokens = tokenize("int a = 0x1F;");
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Keyword,
+                TokenKind::Identifier,
+                TokenKind::Punctuator,
+                TokenKind::Literal,
+                TokenKind::Punctuator,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_skips_comments_and_directives() {
+        let source = "#include <Wire.h>\n// comment\nvoid setup() { /* block\n comment */ }\n";
+        let tokens = tokenize(source);
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, vec!["void", "setup", "(", ")", "{", "}"]);
+    }
+
+    #[test]
+    fn test_tokenize_positions() {
+        let tokens = tokenize("a <<= b;\n  \"x\\\"y\"");
+        assert_eq!(tokens[1].text, "<<=");
+        assert_eq!(tokens[1].start, CodePosition::new(1, 2));
+        assert_eq!(tokens[1].end, CodePosition::new(1, 5));
+        assert_eq!(tokens[4].text, "\"x\\\"y\"");
+        assert_eq!(tokens[4].start, CodePosition::new(2, 2));
+        assert_eq!(tokens[4].end, CodePosition::new(2, 8));
+    }
+
+    #[test]
+    fn test_tokenize_normalization() {
+        let tokens = tokenize("foo(42);");
+        assert_eq!(tokens[0].get_comparison_text(true), "$id");
+        assert_eq!(tokens[0].get_comparison_text(false), "foo");
+        assert_eq!(tokens[2].get_comparison_text(true), "$lit");
+        assert_eq!(tokens[1].get_comparison_text(true), "(");
+    }
+}
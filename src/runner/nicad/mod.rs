@@ -0,0 +1,256 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use log::debug;
+
+mod parser;
+
+use crate::clone_pair::ClonePair;
+use crate::config::nicad::NiCadConfig;
+use crate::config::JobTimeouts;
+use crate::error::{InvalidNiCadReport, RunnerProcessFailedError};
+use crate::job::Job;
+use crate::runner::archive::ArchiveCache;
+use crate::runner::nicad::parser::ReportParser;
+use crate::runner::process::run_with_timeout;
+use crate::runner::workdir::{format_command, record_command, WorkDirs, WORKING_DIR_PLACEHOLDER};
+use crate::runner::{stage_sources, Runner};
+
+/// Returns the threshold in the name of a clone pair report, e.g. 0.3 for
+/// `src_functions-blind-clones-0.30.xml`, or `None` for the other files (e.g. the
+/// `-withsource.xml` and `-classes.xml` variants).
+fn get_report_threshold(file_name: &str) -> Option<f64> {
+    let (_, threshold) = file_name.strip_suffix(".xml")?.rsplit_once("-clones-")?;
+    threshold.parse().ok()
+}
+
+#[derive(Clone)]
+pub struct NiCadRunner {
+    project_path: PathBuf,
+    archives: Arc<ArchiveCache>,
+    config: NiCadConfig,
+    timeouts: JobTimeouts,
+    work_dirs: Arc<WorkDirs>,
+}
+
+impl NiCadRunner {
+    pub fn create(
+        config: NiCadConfig,
+        timeouts: JobTimeouts,
+        project_path: &Path,
+        archives: Arc<ArchiveCache>,
+        work_dirs: Arc<WorkDirs>,
+    ) -> Self {
+        NiCadRunner {
+            project_path: PathBuf::from(project_path),
+            archives,
+            config,
+            timeouts,
+            work_dirs,
+        }
+    }
+
+    fn find_report(&self, working_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        // NiCad names the output directory after the system, the granularity and the
+        // normalization options (e.g. `src_functions-blind-clones`), and the report after the
+        // threshold in its own configuration (e.g. `src_functions-blind-clones-0.30.xml`).
+        let directory_prefix = format!("src_{}", self.config.granularity_to_option_value());
+        for entry in working_dir.read_dir()? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !entry.file_type()?.is_dir()
+                || !name.starts_with(&directory_prefix)
+                || !name.ends_with("-clones")
+            {
+                continue;
+            }
+            for report in entry.path().read_dir()? {
+                let report = report?;
+                let threshold = match get_report_threshold(&report.file_name().to_string_lossy()) {
+                    Some(t) => t,
+                    None => continue,
+                };
+                // NOTE: NiCad takes the threshold only from its configuration. The pairs are
+                // filtered by `threshold`, so a report of a looser threshold is fine, but a
+                // stricter one lacks some of the pairs.
+                if threshold + 1e-9 < self.config.get_threshold() {
+                    return Err(InvalidNiCadReport::new(&format!(
+                        "The NiCad report has the threshold {:.2}, which is lower than the configured {:.2}.",
+                        threshold,
+                        self.config.get_threshold()
+                    ))
+                    .into());
+                }
+                return Ok(report.path());
+            }
+        }
+        Err(InvalidNiCadReport::new("Could not find the clone pair report.").into())
+    }
+
+    fn create_command(&self, working_dir: &Path) -> Command {
+        let mut command = Command::new(self.config.get_executable_path_as_string());
+        command.current_dir(working_dir).args([
+            &self.config.granularity_to_option_value(),
+            &self.config.language_to_option_value(),
+            "src",
+            &self.config.configuration_to_option_value(),
+        ]);
+        command
+    }
+
+    fn run_job_in(&self, job: &Job, working_dir: &Path) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let project_source_name = self
+            .config
+            .get_staged_file_name(&job.get_project().get_file_name()?);
+        let example_source_name = self
+            .config
+            .get_staged_file_name(&job.get_example_sketch().get_file_name()?);
+
+        let sources_path = working_dir.join("src");
+        fs::create_dir(&sources_path)?;
+
+        stage_sources(
+            job,
+            &self.project_path,
+            &self.archives,
+            &sources_path,
+            &project_source_name,
+            &example_source_name,
+        )?;
+
+        let mut command = self.create_command(working_dir);
+        record_command(working_dir, &command)?;
+        let res = run_with_timeout(
+            &mut command,
+            self.timeouts.get_timeout(job.get_library_info()),
+        )?;
+        if res.status.success() {
+            let report_path = self.find_report(working_dir)?;
+            debug!("Reading the report: {}", report_path.to_str().unwrap());
+            let mut file = File::open(report_path)?;
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            let parser = ReportParser::new();
+            let (_, parse_result) = parser
+                .parse_report::<()>(&contents)
+                .map_err(|_| InvalidNiCadReport::new("Could not parse the report."))?;
+            let clone_pairs = parse_result.get_clone_pairs(
+                project_source_name.as_str(),
+                example_source_name.as_str(),
+                self.config.get_minimum_similarity(),
+            );
+            debug!("pairs: {:?}", clone_pairs);
+            Ok(clone_pairs)
+        } else {
+            Err(
+                RunnerProcessFailedError::new(res.status.code().unwrap_or(-1))
+                    .with_output(res.output)
+                    .into(),
+            )
+        }
+    }
+}
+
+impl Runner for NiCadRunner {
+    fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let working_dir = self.work_dirs.create()?;
+        let res = self.run_job_in(&job, working_dir.path());
+        self.work_dirs
+            .finish(working_dir, std::slice::from_ref(&job), res.is_ok());
+        res
+    }
+
+    fn get_command_lines(&self, jobs: &[Job]) -> Vec<String> {
+        let command = format_command(&self.create_command(Path::new(WORKING_DIR_PLACEHOLDER)));
+        jobs.iter().map(|_| command.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use crate::config::nicad::NiCadConfig;
+    use crate::config::Config;
+    use crate::error::InvalidNiCadReport;
+    use crate::runner::archive::ArchiveCache;
+    use crate::runner::nicad::{get_report_threshold, NiCadRunner};
+    use crate::runner::workdir::{KeepWorkDirs, WorkDirs};
+
+    fn create_runner(root: &Path) -> NiCadRunner {
+        let executable_path = root.join("nicad6");
+        fs::write(&executable_path, "").unwrap();
+        let config: Config = toml::from_str(&format!(
+            r#"
+munin_database_root = "~/munin"
+clone_detector_kind = "NiCad"
+number_of_jobs = 1
+
+[clone_detector_config]
+executable_path = "{}"
+granularity = "functions"
+language = "c"
+threshold = "0.30"
+configuration = "default-report"
+"#,
+            executable_path.to_str().unwrap()
+        ))
+        .unwrap();
+        NiCadRunner::create(
+            NiCadConfig::try_from_config(&config).unwrap(),
+            config.get_job_timeouts(),
+            root,
+            Arc::new(ArchiveCache::new(root, 1024)),
+            Arc::new(WorkDirs::new(None, KeepWorkDirs::Never).unwrap()),
+        )
+    }
+
+    #[test]
+    fn test_get_report_threshold() {
+        assert_eq!(
+            get_report_threshold("src_functions-blind-clones-0.30.xml"),
+            Some(0.3)
+        );
+        assert_eq!(
+            get_report_threshold("src_functions-blind-clones-0.30-withsource.xml"),
+            None
+        );
+        assert_eq!(
+            get_report_threshold("src_functions-blind-clones-0.30-classes.xml"),
+            None
+        );
+        assert_eq!(get_report_threshold("src_functions.xml"), None);
+    }
+
+    #[test]
+    fn test_find_report() {
+        let root = tempfile::tempdir().unwrap();
+        let runner = create_runner(root.path());
+        let report_dir = root.path().join("src_functions-blind-clones");
+        fs::create_dir(&report_dir).unwrap();
+        fs::write(
+            report_dir.join("src_functions-blind-clones-0.30-classes.xml"),
+            "",
+        )
+        .unwrap();
+        fs::write(report_dir.join("src_functions-blind-clones-0.20.xml"), "").unwrap();
+        // A stricter report lacks the pairs between the thresholds.
+        let e = runner.find_report(root.path()).unwrap_err();
+        assert!(e.is::<InvalidNiCadReport>());
+
+        fs::remove_file(report_dir.join("src_functions-blind-clones-0.20.xml")).unwrap();
+        fs::write(report_dir.join("src_functions-blind-clones-0.40.xml"), "").unwrap();
+        assert_eq!(
+            runner.find_report(root.path()).unwrap(),
+            report_dir.join("src_functions-blind-clones-0.40.xml")
+        );
+    }
+}
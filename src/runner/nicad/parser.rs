@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use log::debug;
+
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, tag, take_till1, take_until, take_while1};
+use nom::character::complete::{char, multispace0};
+use nom::combinator::{map, map_res, opt};
+use nom::error::{FromExternalError, ParseError};
+use nom::multi::many0;
+use nom::sequence::{delimited, preceded, terminated, tuple};
+use nom::IResult;
+
+use crate::clone_pair::{ClonePair, CodePosition, CodeSlice};
+use crate::error::InvalidNiCadReport;
+
+/*
+= NiCad report grammar (the subset used by Hugin)
+
+report -> item* $
+
+item -> declaration | comment | end_tag | start_tag | text
+
+declaration -> "<?" char* "?>"
+comment -> "<!--" char* "-->"
+end_tag -> "</" name ">"
+start_tag -> "<" name attribute* "/"? ">"
+attribute -> name "=" '"' (^'"')* '"'
+text -> (^'<')+
+
+Only the `clone` elements and their `source` children are interpreted:
+
+clone -> "<clone" ("similarity=" 'digits)? ">" source source "</clone>"
+source -> "<source" "file=" 'string "startline=" 'digits "endline=" 'digits ... ">"
+ */
+
+enum Item {
+    StartTag(String, HashMap<String, String>, bool),
+    EndTag(String),
+    Other,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Source {
+    file: String,
+    start_line: u32,
+    end_line: u32,
+}
+
+impl Source {
+    fn from_attributes(attributes: &HashMap<String, String>) -> Result<Self, InvalidNiCadReport> {
+        let file = attributes
+            .get("file")
+            .ok_or_else(|| InvalidNiCadReport::new("Missing `file` attribute."))?
+            .clone();
+        let start_line = attributes
+            .get("startline")
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| InvalidNiCadReport::new("Invalid `startline` attribute."))?;
+        let end_line = attributes
+            .get("endline")
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| InvalidNiCadReport::new("Invalid `endline` attribute."))?;
+        Ok(Source {
+            file,
+            start_line,
+            end_line,
+        })
+    }
+
+    fn is_file(&self, file_name: &str) -> bool {
+        Path::new(self.file.as_str())
+            .file_name()
+            .is_some_and(|n| n == file_name)
+    }
+
+    fn to_code_slice(&self) -> CodeSlice {
+        CodeSlice::new(
+            CodePosition::new(self.start_line, 0),
+            CodePosition::new(self.end_line, 0),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct ReportedClone {
+    similarity: f64,
+    sources: Vec<Source>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParsedReport {
+    clones: Vec<ReportedClone>,
+}
+
+impl ParsedReport {
+    fn new(clones: Vec<ReportedClone>) -> Self {
+        ParsedReport { clones }
+    }
+
+    pub fn get_clone_pairs(
+        &self,
+        project_file_name: &str,
+        example_source_name: &str,
+        minimum_similarity: f64,
+    ) -> Vec<ClonePair> {
+        // NOTE: Unlike CCFinderSW, NiCad omits the files without any clones from the report, so
+        // a missing file is not an error here.
+        let mut res = Vec::new();
+        for c in &self.clones {
+            if c.similarity < minimum_similarity {
+                debug!("Skipping a clone pair with similarity: {}", c.similarity);
+                continue;
+            }
+            let project_part = c.sources.iter().find(|s| s.is_file(project_file_name));
+            let example_part = c.sources.iter().find(|s| s.is_file(example_source_name));
+            if let (Some(p), Some(e)) = (project_part, example_part) {
+                res.push(ClonePair::new(
+                    p.to_code_slice(),
+                    c.similarity,
+                    e.to_code_slice(),
+                    c.similarity,
+                ));
+            }
+        }
+        res
+    }
+}
+
+pub struct ReportParser {}
+
+impl ReportParser {
+    fn parse_name<'a, E>(&self, input: &'a str) -> IResult<&'a str, String, E>
+    where
+        E: ParseError<&'a str>,
+    {
+        map(
+            take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '-' || c == ':'),
+            String::from,
+        )(input)
+    }
+
+    fn parse_attribute<'a, E>(&self, input: &'a str) -> IResult<&'a str, (String, String), E>
+    where
+        E: ParseError<&'a str>,
+    {
+        tuple((
+            preceded(multispace0, |i| self.parse_name(i)),
+            preceded(tuple((multispace0, char('='), multispace0)), |i| {
+                self.parse_quoted(i)
+            }),
+        ))(input)
+    }
+
+    fn parse_quoted<'a, E>(&self, input: &'a str) -> IResult<&'a str, String, E>
+    where
+        E: ParseError<&'a str>,
+    {
+        map(
+            delimited(char('"'), opt(is_not("\"")), char('"')),
+            |v: Option<&str>| String::from(v.unwrap_or("")),
+        )(input)
+    }
+
+    fn parse_start_tag<'a, E>(&self, input: &'a str) -> IResult<&'a str, Item, E>
+    where
+        E: ParseError<&'a str>,
+    {
+        let start_tag_parser = delimited(
+            char('<'),
+            tuple((
+                |i| self.parse_name(i),
+                many0(|i| self.parse_attribute(i)),
+                preceded(multispace0, opt(char('/'))),
+            )),
+            char('>'),
+        );
+        map(start_tag_parser, |(name, attributes, self_closing)| {
+            Item::StartTag(
+                name,
+                attributes.into_iter().collect(),
+                self_closing.is_some(),
+            )
+        })(input)
+    }
+
+    fn parse_end_tag<'a, E>(&self, input: &'a str) -> IResult<&'a str, Item, E>
+    where
+        E: ParseError<&'a str>,
+    {
+        map(
+            delimited(
+                tag("</"),
+                terminated(|i| self.parse_name(i), multispace0),
+                char('>'),
+            ),
+            Item::EndTag,
+        )(input)
+    }
+
+    fn parse_declaration<'a, E>(&self, input: &'a str) -> IResult<&'a str, Item, E>
+    where
+        E: ParseError<&'a str>,
+    {
+        map(
+            alt((
+                delimited(tag("<?"), take_until("?>"), tag("?>")),
+                delimited(tag("<!--"), take_until("-->"), tag("-->")),
+            )),
+            |_| Item::Other,
+        )(input)
+    }
+
+    fn parse_text<'a, E>(&self, input: &'a str) -> IResult<&'a str, Item, E>
+    where
+        E: ParseError<&'a str>,
+    {
+        map(take_till1(|c| c == '<'), |_| Item::Other)(input)
+    }
+
+    pub fn parse_report<'a, E>(&self, input: &'a str) -> IResult<&'a str, ParsedReport, E>
+    where
+        E: ParseError<&'a str> + FromExternalError<&'a str, InvalidNiCadReport>,
+    {
+        let report_parser = many0(alt((
+            |i| self.parse_declaration(i),
+            |i| self.parse_end_tag(i),
+            |i| self.parse_start_tag(i),
+            |i| self.parse_text(i),
+        )));
+        map_res(report_parser, |items| {
+            let mut clones = Vec::new();
+            let mut current: Option<ReportedClone> = None;
+            for item in items {
+                match item {
+                    Item::StartTag(name, attributes, self_closing) if name == "clone" => {
+                        if current.is_some() {
+                            return Err(InvalidNiCadReport::new("Nested clone elements."));
+                        }
+                        let similarity = match attributes.get("similarity") {
+                            Some(s) => s.parse().map_err(|_| {
+                                InvalidNiCadReport::new("Invalid `similarity` attribute.")
+                            })?,
+                            None => 100.0,
+                        };
+                        let clone = ReportedClone {
+                            similarity,
+                            sources: Vec::new(),
+                        };
+                        if self_closing {
+                            clones.push(clone);
+                        } else {
+                            current = Some(clone);
+                        }
+                    }
+                    Item::StartTag(name, attributes, _) if name == "source" => match &mut current {
+                        Some(c) => c.sources.push(Source::from_attributes(&attributes)?),
+                        None => {
+                            return Err(InvalidNiCadReport::new(
+                                "A source element outside of a clone element.",
+                            ))
+                        }
+                    },
+                    Item::EndTag(name) if name == "clone" => match current.take() {
+                        Some(c) => clones.push(c),
+                        None => return Err(InvalidNiCadReport::new("Unbalanced clone elements.")),
+                    },
+                    _ => {}
+                }
+            }
+            if current.is_some() {
+                Err(InvalidNiCadReport::new("Unterminated clone element."))
+            } else {
+                Ok(ParsedReport::new(clones))
+            }
+        })(input)
+    }
+
+    pub fn new() -> Self {
+        ReportParser {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clone_pair::{ClonePair, CodePosition, CodeSlice};
+    use crate::runner::nicad::parser::{ParsedReport, ReportParser, ReportedClone, Source};
+
+    const REPORT: &str = r#"<?xml version="1.0"?>
+<clones>
+<systeminfo processor="nicad6" system="src" granularity="functions" threshold="30%" minlines="10" maxlines="2500"/>
+<cloneinfo npcs="12" npairs="2"/>
+<runinfo ncompares="66" cputime="4"/>
+<clone nlines="12" similarity="100">
+<source file="src/MyProject.ino.c" startline="130" endline="141" pcid="3"></source>
+<source file="src/Example.ino.c" startline="20" endline="30" pcid="7"></source>
+</clone>
+<clone nlines="10" similarity="75">
+<source file="src/Example.ino.c" startline="40" endline="49" pcid="8"/>
+<source file="src/MyProject.ino.c" startline="1" endline="10" pcid="1"/>
+</clone>
+</clones>
+"#;
+
+    #[test]
+    fn test_parse_start_tag() {
+        let parser = ReportParser::new();
+        let res = parser.parse_start_tag::<nom::error::VerboseError<&str>>(
+            r#"<source file="a.c" startline="1" endline="2"/>"#,
+        );
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let parser = ReportParser::new();
+        let (left, res) = parser
+            .parse_report::<nom::error::VerboseError<&str>>(REPORT)
+            .unwrap_or_else(|e| panic!("Parse failed: {}", e));
+        assert!(left.is_empty());
+        let expected = ParsedReport::new(vec![
+            ReportedClone {
+                similarity: 100.0,
+                sources: vec![
+                    Source {
+                        file: String::from("src/MyProject.ino.c"),
+                        start_line: 130,
+                        end_line: 141,
+                    },
+                    Source {
+                        file: String::from("src/Example.ino.c"),
+                        start_line: 20,
+                        end_line: 30,
+                    },
+                ],
+            },
+            ReportedClone {
+                similarity: 75.0,
+                sources: vec![
+                    Source {
+                        file: String::from("src/Example.ino.c"),
+                        start_line: 40,
+                        end_line: 49,
+                    },
+                    Source {
+                        file: String::from("src/MyProject.ino.c"),
+                        start_line: 1,
+                        end_line: 10,
+                    },
+                ],
+            },
+        ]);
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn test_get_clone_pairs() {
+        let parser = ReportParser::new();
+        let (_, res) = parser
+            .parse_report::<nom::error::VerboseError<&str>>(REPORT)
+            .unwrap();
+        let pairs = res.get_clone_pairs("MyProject.ino.c", "Example.ino.c", 80.0);
+        assert_eq!(
+            pairs,
+            vec![ClonePair::new(
+                CodeSlice::new(CodePosition::new(130, 0), CodePosition::new(141, 0)),
+                100.0,
+                CodeSlice::new(CodePosition::new(20, 0), CodePosition::new(30, 0)),
+                100.0,
+            )]
+        );
+        let pairs = res.get_clone_pairs("MyProject.ino.c", "Example.ino.c", 70.0);
+        assert_eq!(pairs.len(), 2);
+        assert!(res
+            .get_clone_pairs("Other.ino.c", "Unknown.ino.c", 70.0)
+            .is_empty());
+    }
+}
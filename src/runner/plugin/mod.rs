@@ -0,0 +1,98 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::debug;
+
+pub mod pool;
+pub mod protocol;
+
+use crate::clone_pair::ClonePair;
+use crate::config::plugin::PluginConfig;
+use crate::config::JobTimeouts;
+use crate::job::Job;
+use crate::runner::archive::ArchiveCache;
+use crate::runner::plugin::pool::PluginPool;
+use crate::runner::plugin::protocol::Request;
+use crate::runner::workdir::WorkDirs;
+use crate::runner::{stage_sources, Runner};
+
+/// Runs the jobs on long-running plugin processes speaking the protocol in `protocol`.
+#[derive(Clone)]
+pub struct PluginRunner {
+    project_path: PathBuf,
+    archives: Arc<ArchiveCache>,
+    config: PluginConfig,
+    timeouts: JobTimeouts,
+    work_dirs: Arc<WorkDirs>,
+    pool: Arc<PluginPool>,
+}
+
+impl PluginRunner {
+    pub fn create(
+        config: PluginConfig,
+        timeouts: JobTimeouts,
+        project_path: &Path,
+        archives: Arc<ArchiveCache>,
+        work_dirs: Arc<WorkDirs>,
+    ) -> Self {
+        PluginRunner {
+            project_path: PathBuf::from(project_path),
+            archives,
+            pool: Arc::new(PluginPool::new(config.clone())),
+            config,
+            timeouts,
+            work_dirs,
+        }
+    }
+
+    fn run_job_in(&self, job: &Job, working_dir: &Path) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let project_source_name = job.get_project().get_file_name()?;
+        let example_source_name = job.get_example_sketch().get_file_name()?;
+
+        let sources_path = working_dir.join(self.config.get_sources_dir());
+        fs::create_dir_all(&sources_path)?;
+        stage_sources(
+            job,
+            &self.project_path,
+            &self.archives,
+            &sources_path,
+            &project_source_name,
+            &example_source_name,
+        )?;
+
+        let id = job.get_id();
+        let project_file = sources_path.join(&project_source_name);
+        let example_file = sources_path.join(&example_source_name);
+        let request = Request::Job {
+            id: &id,
+            job,
+            working_dir: working_dir.to_str().unwrap(),
+            project_file: project_file.to_str().unwrap(),
+            example_file: example_file.to_str().unwrap(),
+        };
+        // NOTE: The request is kept in the working directory so that the job can be sent to the
+        // plugin by hand.
+        serde_json::to_writer(File::create(working_dir.join("request.json"))?, &request)?;
+        debug!("Sending a request to the plugin: {:?}", request);
+        let clone_pairs = self.pool.run(
+            &id,
+            &request,
+            self.timeouts.get_timeout(job.get_library_info()),
+        )?;
+        debug!("pairs: {:?}", clone_pairs);
+        Ok(clone_pairs)
+    }
+}
+
+impl Runner for PluginRunner {
+    fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        let working_dir = self.work_dirs.create()?;
+        let res = self.run_job_in(&job, working_dir.path());
+        self.work_dirs
+            .finish(working_dir, std::slice::from_ref(&job), res.is_ok());
+        res
+    }
+}
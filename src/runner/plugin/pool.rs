@@ -0,0 +1,352 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+
+use crate::cancel;
+use crate::clone_pair::ClonePair;
+use crate::config::plugin::PluginConfig;
+use crate::error::{JobCancelledError, RunnerProcessFailedError, WorkerFailedError};
+use crate::runner::plugin::protocol::{Request, Response, PROTOCOL_VERSION};
+use crate::runner::process::{capture, kill_process_group, CapturedOutput, Watchdog};
+
+/// The time limit of the handshake, which includes the startup of the plugin.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The time given to the plugin to exit after `Request::Shutdown`.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A running plugin process which finished the handshake.
+struct Plugin {
+    child: Child,
+    requests: ChildStdin,
+    responses: BufReader<ChildStdout>,
+    stderr: Option<JoinHandle<(String, bool)>>,
+    exited: bool,
+}
+
+impl Plugin {
+    fn spawn(config: &PluginConfig) -> Result<Self, Box<dyn Error>> {
+        let command = config.get_command();
+        info!("Starting a plugin: {}", command.join(" "));
+        let mut child = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()?;
+        let requests = child.stdin.take().unwrap();
+        let responses = BufReader::new(child.stdout.take().unwrap());
+        let stderr = Some(capture(child.stderr.take().unwrap()));
+        let mut plugin = Plugin {
+            child,
+            requests,
+            responses,
+            stderr,
+            exited: false,
+        };
+        let hello = Request::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            hugin_version: env!("CARGO_PKG_VERSION"),
+            settings: config.get_settings(),
+        };
+        match plugin.exchange(&hello, Some(STARTUP_TIMEOUT))? {
+            Response::Ready {
+                protocol_version,
+                name,
+            } => {
+                if protocol_version != PROTOCOL_VERSION {
+                    return Err(WorkerFailedError::new(
+                        format!(
+                            "The plugin `{}` speaks the protocol version {}, but Hugin speaks {}.",
+                            name, protocol_version, PROTOCOL_VERSION
+                        )
+                        .as_str(),
+                    )
+                    .into());
+                }
+                info!("The plugin is ready: {}", name);
+                Ok(plugin)
+            }
+            response => Err(WorkerFailedError::new(
+                format!("Unexpected greeting: {:?}", response).as_str(),
+            )
+            .into()),
+        }
+    }
+
+    fn has_exited(&mut self) -> bool {
+        if !self.exited {
+            self.exited = !matches!(self.child.try_wait(), Ok(None));
+        }
+        self.exited
+    }
+
+    fn send(&mut self, request: &Request) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.requests, request)?;
+        writeln!(self.requests)?;
+        self.requests.flush()?;
+        Ok(())
+    }
+
+    fn read_response(&mut self) -> Result<Response, Box<dyn Error>> {
+        let mut line = String::new();
+        if self.responses.read_line(&mut line)? == 0 {
+            return Err(self.collect_crash());
+        }
+        debug!("Received a response from the plugin: {}", line.trim_end());
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    /// Sends the request and waits for the response.
+    ///
+    /// A request exceeding the time limit (or cancelled) kills the plugin, which makes the
+    /// pending read fail.
+    fn exchange(
+        &mut self,
+        request: &Request,
+        timeout: Option<Duration>,
+    ) -> Result<Response, Box<dyn Error>> {
+        let watchdog = Watchdog::start(self.child.id(), timeout);
+        let response = self.send(request).and_then(|_| self.read_response());
+        if let Some(reason) = watchdog.stop() {
+            // The killed plugin closed its output, so the failed read already collected it.
+            let output = match response {
+                Err(e) => e
+                    .downcast_ref::<RunnerProcessFailedError>()
+                    .and_then(|e| e.get_output())
+                    .cloned(),
+                Ok(_) => None,
+            };
+            let output = output.unwrap_or_else(|| self.collect_output());
+            return Err(reason.into_error_with_output(output));
+        }
+        response
+    }
+
+    /// Kills the plugin and returns the end of its `stderr`.
+    fn collect_output(&mut self) -> CapturedOutput {
+        // NOTE: The plugin may have left its children holding `stderr` open.
+        kill_process_group(self.child.id());
+        self.exited = true;
+        let _ = self.child.wait();
+        let (stderr, truncated) = self
+            .stderr
+            .take()
+            .map(|h| h.join().unwrap_or_default())
+            .unwrap_or_default();
+        CapturedOutput {
+            stdout: String::new(),
+            stderr,
+            truncated,
+        }
+    }
+
+    /// Returns the error of the plugin which closed its output, with the end of its `stderr`.
+    fn collect_crash(&mut self) -> Box<dyn Error> {
+        let output = self.collect_output();
+        let status_code = self.child.wait().ok().and_then(|s| s.code()).unwrap_or(-1);
+        RunnerProcessFailedError::new(status_code)
+            .with_output(output)
+            .into()
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        if self.has_exited() {
+            let _ = self.child.wait();
+            return;
+        }
+        if self.send(&Request::Shutdown).is_ok() {
+            let watchdog = Watchdog::start(self.child.id(), Some(SHUTDOWN_TIMEOUT));
+            let _ = self.child.wait();
+            watchdog.stop();
+        } else {
+            kill_process_group(self.child.id());
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// Keeps the idle plugins so that each thread reuses a running plugin.
+pub struct PluginPool {
+    config: PluginConfig,
+    idle: Mutex<Vec<Plugin>>,
+}
+
+impl PluginPool {
+    pub fn new(config: PluginConfig) -> Self {
+        PluginPool {
+            config,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns an idle plugin, or starts a new one if there is none or the idle ones exited.
+    fn take_plugin(&self) -> Result<Plugin, Box<dyn Error>> {
+        loop {
+            let idle_plugin = self.idle.lock().unwrap().pop();
+            match idle_plugin {
+                Some(mut p) => {
+                    if !p.has_exited() {
+                        return Ok(p);
+                    }
+                    warn!("A plugin exited while idle, starting a new one...");
+                }
+                None => return Plugin::spawn(&self.config),
+            }
+        }
+    }
+
+    /// Sends the job request `id` to a plugin and returns the clone pairs it found.
+    ///
+    /// A plugin which crashed, timed out or broke the protocol is discarded, and a new one is
+    /// started for the next job.
+    pub fn run(
+        &self,
+        id: &str,
+        request: &Request,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+        if cancel::is_cancelled() {
+            return Err(JobCancelledError::new().into());
+        }
+        let mut plugin = self.take_plugin()?;
+        match plugin.exchange(request, timeout)? {
+            Response::Result {
+                id: response_id,
+                clone_pairs,
+            } if response_id == id => {
+                self.idle.lock().unwrap().push(plugin);
+                Ok(clone_pairs)
+            }
+            Response::Error {
+                id: response_id,
+                message,
+            } if response_id == id => {
+                self.idle.lock().unwrap().push(plugin);
+                Err(WorkerFailedError::new(message.as_str()).into())
+            }
+            response => Err(WorkerFailedError::new(
+                format!("Unexpected response: {:?}", response).as_str(),
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use crate::config::plugin::PluginConfig;
+    use crate::config::Config;
+    use crate::error::{JobTimedOutError, RunnerProcessFailedError, WorkerFailedError};
+    use crate::job::test_job;
+    use crate::runner::plugin::pool::PluginPool;
+    use crate::runner::plugin::protocol::Request;
+
+    fn create_pool(script_path: &Path, script: &str) -> PluginPool {
+        fs::write(script_path, script).unwrap();
+        let config: Config = toml::from_str(&format!(
+            r#"
+munin_database_root = "~/munin"
+clone_detector_kind = "Plugin"
+number_of_jobs = 1
+
+[clone_detector_config]
+command = "sh {}"
+"#,
+            script_path.to_str().unwrap()
+        ))
+        .unwrap();
+        PluginPool::new(PluginConfig::try_from_config(&config).unwrap())
+    }
+
+    fn run(
+        pool: &PluginPool,
+        example: &str,
+        timeout: Option<Duration>,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let job = test_job(example);
+        let id = job.get_id();
+        let request = Request::Job {
+            id: &id,
+            job: &job,
+            working_dir: "/tmp",
+            project_file: "/tmp/src/MyProject.ino",
+            example_file: "/tmp/src/Example.ino",
+        };
+        pool.run(&id, &request, timeout).map(|pairs| pairs.len())
+    }
+
+    #[test]
+    fn test_plugin_pool() {
+        let root = tempfile::tempdir().unwrap();
+        let pool = create_pool(
+            &root.path().join("plugin.sh"),
+            r#"
+read hello
+echo '{"type":"ready","protocol_version":1,"name":"stub"}'
+while read request; do
+    id=$(echo "$request" | sed 's/.*"id":"\([^"]*\)".*/\1/')
+    case "$request" in
+        *'"type":"shutdown"'*) exit 0 ;;
+        *Crash*) echo 'Segmentation fault' >&2; exit 3 ;;
+        *Slow*) echo 'Still working' >&2; sleep 10 ;;
+        *Broken*) echo "{\"type\":\"error\",\"id\":\"$id\",\"message\":\"broken\"}" ;;
+        *) echo "{\"type\":\"result\",\"id\":\"$id\",\"clone_pairs\":[]}" ;;
+    esac
+done
+"#,
+        );
+        assert_eq!(run(&pool, "Example/Example.ino", None).unwrap(), 0);
+        let e = run(&pool, "Broken/Broken.ino", None).unwrap_err();
+        assert!(e.is::<WorkerFailedError>());
+        assert_eq!(pool.idle.lock().unwrap().len(), 1);
+
+        let e = run(&pool, "Crash/Crash.ino", None).unwrap_err();
+        let output = e
+            .downcast_ref::<RunnerProcessFailedError>()
+            .unwrap()
+            .get_output()
+            .unwrap();
+        assert_eq!(output.stderr, "Segmentation fault\n");
+        assert!(pool.idle.lock().unwrap().is_empty());
+        // A new plugin takes the next job.
+        assert_eq!(run(&pool, "Example/Example.ino", None).unwrap(), 0);
+
+        let e = run(&pool, "Slow/Slow.ino", Some(Duration::from_millis(500))).unwrap_err();
+        let output = e
+            .downcast_ref::<JobTimedOutError>()
+            .unwrap()
+            .get_output()
+            .unwrap();
+        assert_eq!(output.stderr, "Still working\n");
+        assert!(pool.idle.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_protocol_version_mismatch() {
+        let root = tempfile::tempdir().unwrap();
+        let pool = create_pool(
+            &root.path().join("plugin.sh"),
+            r#"
+read hello
+echo '{"type":"ready","protocol_version":2,"name":"future"}'
+read request
+"#,
+        );
+        let e = run(&pool, "Example/Example.ino", None).unwrap_err();
+        assert!(e.to_string().contains("protocol version 2"));
+    }
+}
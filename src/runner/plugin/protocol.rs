@@ -0,0 +1,111 @@
+//! The messages exchanged with a plugin, one JSON object per line.
+//!
+//! See `tools/plugin/README.adoc` for the description of the protocol.
+
+use std::collections::HashMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::clone_pair::ClonePair;
+use crate::job::Job;
+
+/// The version of the protocol spoken by this version of Hugin.
+///
+/// It is incremented whenever a message changes in a way which breaks the existing plugins.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The messages sent to the plugin.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request<'a> {
+    /// The first message after the plugin starts.
+    Hello {
+        protocol_version: u32,
+        hugin_version: &'a str,
+        settings: &'a HashMap<String, String>,
+    },
+    /// Asks the plugin to check the staged sources of the job.
+    Job {
+        id: &'a str,
+        job: &'a Job,
+        working_dir: &'a str,
+        project_file: &'a str,
+        example_file: &'a str,
+    },
+    /// Asks the plugin to exit.
+    Shutdown,
+}
+
+/// The messages sent by the plugin.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// The reply to `Request::Hello`.
+    Ready { protocol_version: u32, name: String },
+    /// The clone pairs found in the job.
+    Result {
+        id: String,
+        #[serde(default)]
+        clone_pairs: Vec<ClonePair>,
+    },
+    /// The plugin could not check the job but can take the next one.
+    Error { id: String, message: String },
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::clone_pair::{ClonePair, CodePosition, CodeSlice};
+    use crate::runner::plugin::protocol::{Request, Response};
+
+    #[test]
+    fn test_messages() {
+        let settings: HashMap<String, String> = [(String::from("threshold"), String::from("0.8"))]
+            .iter()
+            .cloned()
+            .collect();
+        let hello = Request::Hello {
+            protocol_version: 1,
+            hugin_version: "0.1.0",
+            settings: &settings,
+        };
+        assert_eq!(
+            serde_json::to_string(&hello).unwrap(),
+            r#"{"type":"hello","protocol_version":1,"hugin_version":"0.1.0","settings":{"threshold":"0.8"}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Request::Shutdown).unwrap(),
+            r#"{"type":"shutdown"}"#
+        );
+
+        let response: Response = serde_json::from_str(
+            r#"{"type":"result","id":"0123","clone_pairs":[{
+                "project":{"start":{"lines":1,"columns":0},"end":{"lines":2,"columns":5}},
+                "example_sketch":{"start":{"lines":3,"columns":0},"end":{"lines":4,"columns":5}},
+                "scores":{"project_part":90.0,"example_sketch_part":80.0}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response,
+            Response::Result {
+                id: String::from("0123"),
+                clone_pairs: vec![ClonePair::new(
+                    CodeSlice::new(CodePosition::new(1, 0), CodePosition::new(2, 5)),
+                    90.0,
+                    CodeSlice::new(CodePosition::new(3, 0), CodePosition::new(4, 5)),
+                    80.0,
+                )],
+            }
+        );
+        let response: Response =
+            serde_json::from_str(r#"{"type":"ready","protocol_version":1,"name":"stub"}"#).unwrap();
+        assert_eq!(
+            response,
+            Response::Ready {
+                protocol_version: 1,
+                name: String::from("stub")
+            }
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::error::Error;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::cancel;
+use crate::error::{JobCancelledError, JobTimedOutError};
+
+/// Kills the process group led by the child process.
+///
+/// The detectors may spawn their own children (e.g. the JVM or the NiCad scripts), so killing
+/// only the child would leave them running.
+pub fn kill_process_group(child_id: u32) {
+    // SAFETY: `kill` has no memory safety requirements.
+    let res = unsafe { libc::kill(-(child_id as libc::pid_t), libc::SIGKILL) };
+    if res != 0 {
+        warn!("Could not kill the process group: {}", child_id);
+    }
+}
+
+/// The maximum size of the output kept for each stream of a detector process.
+const MAX_CAPTURED_OUTPUT: usize = 64 * 1024;
+
+/// The standard output and error of a detector process.
+///
+/// Only the last `MAX_CAPTURED_OUTPUT` bytes of each stream are kept because the errors are
+/// usually at the end.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CapturedOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub truncated: bool,
+}
+
+#[derive(Debug)]
+pub struct ProcessOutput {
+    pub status: ExitStatus,
+    pub output: CapturedOutput,
+}
+
+/// Reads the stream on its own thread until it is closed, keeping the last
+/// `MAX_CAPTURED_OUTPUT` bytes.
+pub fn capture<R: Read + Send + 'static>(mut reader: R) -> JoinHandle<(String, bool)> {
+    thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut truncated = false;
+        let mut buf = [0u8; 8192];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => kept.extend_from_slice(&buf[..n]),
+            }
+            // NOTE: Dropping the head only once in a while keeps the copying cheap.
+            if kept.len() > 2 * MAX_CAPTURED_OUTPUT {
+                kept.drain(..kept.len() - MAX_CAPTURED_OUTPUT);
+                truncated = true;
+            }
+        }
+        if kept.len() > MAX_CAPTURED_OUTPUT {
+            kept.drain(..kept.len() - MAX_CAPTURED_OUTPUT);
+            truncated = true;
+        }
+        (String::from_utf8_lossy(&kept).into_owned(), truncated)
+    })
+}
+
+/// The interval of checking whether the run is cancelled.
+const CANCELLATION_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The reason why the watchdog killed the process.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KillReason {
+    TimedOut(Duration),
+    Cancelled,
+}
+
+impl KillReason {
+    /// Returns the error with the output of the killed process attached.
+    pub fn into_error_with_output(self, output: CapturedOutput) -> Box<dyn Error> {
+        match self {
+            KillReason::TimedOut(timeout) => {
+                JobTimedOutError::new(timeout).with_output(output).into()
+            }
+            KillReason::Cancelled => JobCancelledError::new().with_output(output).into(),
+        }
+    }
+}
+
+/// Kills the process group of a child process if it is not stopped before the time limit or
+/// the run is cancelled.
+pub struct Watchdog {
+    stop: Sender<()>,
+    handle: JoinHandle<Option<KillReason>>,
+}
+
+impl Watchdog {
+    pub fn start(child_id: u32, timeout: Option<Duration>) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let handle = thread::spawn(move || loop {
+            let interval = match deadline {
+                Some(d) => d
+                    .saturating_duration_since(Instant::now())
+                    .min(CANCELLATION_CHECK_INTERVAL),
+                None => CANCELLATION_CHECK_INTERVAL,
+            };
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {
+                    if cancel::is_cancelled() {
+                        kill_process_group(child_id);
+                        return Some(KillReason::Cancelled);
+                    }
+                    if let (Some(timeout), Some(deadline)) = (timeout, deadline) {
+                        if Instant::now() >= deadline {
+                            warn!("Killing the detector after {:?}...", timeout);
+                            kill_process_group(child_id);
+                            return Some(KillReason::TimedOut(timeout));
+                        }
+                    }
+                }
+                _ => return None,
+            }
+        });
+        Watchdog { stop, handle }
+    }
+
+    /// Stops the watchdog and returns why the process was killed if it was.
+    pub fn stop(self) -> Option<KillReason> {
+        let _ = self.stop.send(());
+        self.handle.join().unwrap_or(None)
+    }
+}
+
+/// Runs the command in a new process group and waits for it to exit, capturing its output.
+///
+/// If the process doesn't exit within the time limit or the run is cancelled, the whole group
+/// is killed and `JobTimedOutError` or `JobCancelledError` is returned with the output so far.
+pub fn run_with_timeout(
+    command: &mut Command,
+    timeout: Option<Duration>,
+) -> Result<ProcessOutput, Box<dyn Error>> {
+    if cancel::is_cancelled() {
+        return Err(JobCancelledError::new().into());
+    }
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+    let stdout = capture(child.stdout.take().unwrap());
+    let stderr = capture(child.stderr.take().unwrap());
+    let watchdog = Watchdog::start(child.id(), timeout);
+    let status = child.wait();
+    let killed = watchdog.stop();
+    let (stdout, stdout_truncated) = stdout.join().unwrap_or_default();
+    let (stderr, stderr_truncated) = stderr.join().unwrap_or_default();
+    let output = CapturedOutput {
+        stdout,
+        stderr,
+        truncated: stdout_truncated || stderr_truncated,
+    };
+    match killed {
+        Some(reason) => Err(reason.into_error_with_output(output)),
+        None => Ok(ProcessOutput {
+            status: status?,
+            output,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    use crate::error::JobTimedOutError;
+    use crate::runner::process::{run_with_timeout, MAX_CAPTURED_OUTPUT};
+
+    #[test]
+    fn test_run_with_timeout() {
+        let res = run_with_timeout(
+            Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]),
+            Some(Duration::from_secs(10)),
+        )
+        .unwrap();
+        assert_eq!(res.status.code(), Some(3));
+        assert_eq!(res.output.stdout, "out\n");
+        assert_eq!(res.output.stderr, "err\n");
+        assert!(!res.output.truncated);
+
+        let res = run_with_timeout(
+            Command::new("sh").args(["-c", "head -c 200000 /dev/zero | tr '\\0' a; echo b"]),
+            None,
+        )
+        .unwrap();
+        assert_eq!(res.output.stdout.len(), MAX_CAPTURED_OUTPUT);
+        assert!(res.output.stdout.ends_with("ab\n"));
+        assert!(res.output.truncated);
+
+        // The grandchild `sleep` must be killed together with the shell.
+        let start = Instant::now();
+        let res = run_with_timeout(
+            Command::new("sh").args(["-c", "echo started; sleep 10; true"]),
+            Some(Duration::from_millis(200)),
+        );
+        let e = res.unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // The output so far is kept for the job log.
+        let output = e
+            .downcast_ref::<JobTimedOutError>()
+            .unwrap()
+            .get_output()
+            .unwrap();
+        assert_eq!(output.stdout, "started\n");
+    }
+}
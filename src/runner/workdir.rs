@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use log::info;
+
+use tempfile::TempDir;
+
+use crate::job::Job;
+
+/// Stands for the working directory in the command lines of the execution plan.
+pub const WORKING_DIR_PLACEHOLDER: &str = "<working_dir>";
+
+/// The working directories to be kept after their jobs finish.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeepWorkDirs {
+    Never,
+    Failed,
+    All,
+}
+
+impl KeepWorkDirs {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "failed" => Some(KeepWorkDirs::Failed),
+            "all" => Some(KeepWorkDirs::All),
+            _ => None,
+        }
+    }
+}
+
+/// Creates the working directories of the detectors and keeps them for debugging if requested.
+///
+/// The locations of the kept directories are remembered until the results of their jobs are
+/// created.
+pub struct WorkDirs {
+    root: PathBuf,
+    keep: KeepWorkDirs,
+    kept: Mutex<HashMap<String, Vec<PathBuf>>>,
+}
+
+impl WorkDirs {
+    /// Creates the working directories in `root`, or in the temporary directory of the system if
+    /// it is `None`.
+    pub fn new(root: Option<&Path>, keep: KeepWorkDirs) -> Result<Self, Box<dyn Error>> {
+        let root = match root {
+            Some(r) => {
+                fs::create_dir_all(r)?;
+                PathBuf::from(r)
+            }
+            None => std::env::temp_dir(),
+        };
+        Ok(WorkDirs {
+            root,
+            keep,
+            kept: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn create(&self) -> Result<TempDir, Box<dyn Error>> {
+        Ok(tempfile::Builder::new()
+            .prefix("hugin-")
+            .tempdir_in(&self.root)?)
+    }
+
+    /// Removes the working directory of the jobs, or keeps it if the mode says so.
+    pub fn finish(&self, working_dir: TempDir, jobs: &[Job], succeeded: bool) {
+        let keep = match self.keep {
+            KeepWorkDirs::Never => false,
+            KeepWorkDirs::Failed => !succeeded,
+            KeepWorkDirs::All => true,
+        };
+        if !keep {
+            return;
+        }
+        let path = working_dir.into_path();
+        info!("Keeping the working directory: {}", path.to_str().unwrap());
+        let mut kept = self.kept.lock().unwrap();
+        for job in jobs {
+            kept.entry(job.get_id()).or_default().push(path.clone());
+        }
+    }
+
+    /// Returns the kept working directories of the job and forgets them.
+    pub fn take_kept(&self, job: &Job) -> Vec<PathBuf> {
+        self.kept
+            .lock()
+            .unwrap()
+            .remove(&job.get_id())
+            .unwrap_or_default()
+    }
+}
+
+fn quote_argument(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c))
+    {
+        String::from(arg)
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Formats the command as a shell command line.
+pub fn format_command(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|a| quote_argument(&a.to_string_lossy()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Writes the command line to `command.txt` in the working directory so that the detector can
+/// be rerun by hand.
+pub fn record_command(working_dir: &Path, command: &Command) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(working_dir.join("command.txt"))?;
+    writeln!(file, "{}", format_command(command))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::process::Command;
+
+    use crate::job::test_job;
+    use crate::runner::workdir::{record_command, KeepWorkDirs, WorkDirs};
+
+    #[test]
+    fn test_work_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        let job = test_job("Example/Example.ino");
+        let work_dirs = WorkDirs::new(Some(root.path()), KeepWorkDirs::Failed).unwrap();
+
+        let succeeded = work_dirs.create().unwrap();
+        let succeeded_path = succeeded.path().to_path_buf();
+        work_dirs.finish(succeeded, std::slice::from_ref(&job), true);
+        assert!(!succeeded_path.exists());
+        assert!(work_dirs.take_kept(&job).is_empty());
+
+        let failed = work_dirs.create().unwrap();
+        record_command(
+            failed.path(),
+            Command::new("ccfindersw").args(["-d", "src", "it's"]),
+        )
+        .unwrap();
+        work_dirs.finish(failed, std::slice::from_ref(&job), false);
+        let kept = work_dirs.take_kept(&job);
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].starts_with(root.path()));
+        assert_eq!(
+            fs::read_to_string(kept[0].join("command.txt")).unwrap(),
+            "ccfindersw -d src 'it'\\''s'\n"
+        );
+        assert!(work_dirs.take_kept(&job).is_empty());
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::VecDeque;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+
+use log::{error, info, warn};
+
+use crate::cancel;
+use crate::error::{JobCancelledError, JobTimedOutError};
+use crate::job::{Job, JobError, JobErrorKind, JobResult};
+use crate::runner::workdir::WorkDirs;
+use crate::runner::Runner;
+
+/// Describes the batch in a line, e.g. for a progress bar.
+pub fn describe_batch(batch: &[Job]) -> String {
+    let job = &batch[0];
+    let description = format!(
+        "{} ({})",
+        job.get_example_sketch().get_location(),
+        job.get_library_info().get_name()
+    );
+    if batch.len() > 1 {
+        format!("{} and {} more job(s)", description, batch.len() - 1)
+    } else {
+        description
+    }
+}
+
+/// Runs the batch and creates the results of the jobs, including the failed ones.
+///
+/// A panic in the runner fails the jobs in the batch instead of killing the thread.
+fn run_batch<R>(runner: &R, batch: &[Job]) -> Vec<JobResult>
+where
+    R: Runner + ?Sized,
+{
+    match panic::catch_unwind(AssertUnwindSafe(|| runner.run_batch(batch))) {
+        Ok(outcomes) => batch
+            .iter()
+            .zip(outcomes)
+            .map(|(j, outcome)| match outcome {
+                Ok(pairs) => j.create_result(pairs),
+                Err(e) => {
+                    if e.is::<JobTimedOutError>() {
+                        warn!("Job timed out: {}", describe_batch(std::slice::from_ref(j)));
+                    } else if e.is::<JobCancelledError>() {
+                        info!("Job cancelled: {}", describe_batch(std::slice::from_ref(j)));
+                    } else {
+                        error!("Job failed with error: {:?}", e);
+                    }
+                    j.create_error_result(e.as_ref())
+                }
+            })
+            .collect(),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|m| String::from(*m))
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("unknown panic"));
+            error!("The runner panicked: {}", message);
+            let error = JobError::new(JobErrorKind::Panic, message.as_str());
+            batch
+                .iter()
+                .map(|j| j.create_failed_result(error.clone()))
+                .collect()
+        }
+    }
+}
+
+/// Receives the progress and the results of `run_jobs`.
+///
+/// The callbacks are called from the worker threads, so they must be cheap and must not block.
+pub trait Progress {
+    /// Called once before any job runs.
+    fn started(&self, _number_of_jobs: usize, _number_of_threads: usize) {}
+
+    /// Called when the thread starts running the batch.
+    fn batch_started(&self, _thread: usize, _batch: &[Job]) {}
+
+    /// Called with the result of each job, including the failed, cancelled and skipped ones.
+    fn job_finished(&self, result: &JobResult);
+
+    /// Called when the thread runs out of batches or the run is cancelled.
+    ///
+    /// The threads of a `JobPool` go on with the batches of the other runs, so it is called after
+    /// each batch there.
+    fn thread_finished(&self, _thread: usize) {}
+
+    /// Called once after all results are reported.
+    fn finished(&self) {}
+}
+
+/// Runs the batches on `number_of_threads` threads.
+///
+/// The batches are kept in a shared queue and each thread takes the next one as soon as it
+/// finishes the previous one, so that a thread which drew large libraries doesn't keep the
+/// others waiting. Every job gets a result, whether it succeeded or not.
+///
+/// Each result is passed to `progress` as soon as its job finishes, along with the working
+/// directories kept for it.
+pub fn run_jobs<R, P>(
+    batches: Vec<Vec<Job>>,
+    runner: Arc<R>,
+    work_dirs: Arc<WorkDirs>,
+    progress: Arc<P>,
+    number_of_threads: usize,
+) where
+    R: Runner + Sync + Send + ?Sized + 'static,
+    P: Progress + Sync + Send + ?Sized + 'static,
+{
+    let number_of_jobs: usize = batches.iter().map(|b| b.len()).sum();
+    let number_of_threads = number_of_threads.max(1).min(batches.len().max(1));
+    info!("Running detector on {} thread(s)...", number_of_threads);
+    progress.started(number_of_jobs, number_of_threads);
+
+    let queue = Arc::new(Mutex::new(VecDeque::from(batches)));
+    let threads = (0..number_of_threads)
+        .map(|i| {
+            let queue = queue.clone();
+            let runner = runner.clone();
+            let work_dirs = work_dirs.clone();
+            let progress = progress.clone();
+            thread::spawn(move || {
+                // NOTE: The jobs are not dispatched any more once the run is cancelled.
+                while !cancel::is_cancelled() {
+                    let batch = queue.lock().unwrap().pop_front();
+                    let batch = match batch {
+                        Some(b) => b,
+                        None => break,
+                    };
+                    progress.batch_started(i, &batch);
+                    for job_result in run_batch(runner.as_ref(), &batch) {
+                        let kept = work_dirs.take_kept(job_result.get_job());
+                        progress.job_finished(&job_result.with_workdirs(kept));
+                    }
+                }
+                progress.thread_finished(i);
+            })
+        })
+        .collect::<Vec<JoinHandle<()>>>();
+
+    for t in threads {
+        if let Err(e) = t.join() {
+            error!("A thread failed with error: {:?}", e);
+        }
+    }
+    // The batches are left in the queue only if the run was cancelled or all threads died.
+    for batch in queue.lock().unwrap().drain(..) {
+        for j in &batch {
+            let job_result = if cancel::is_cancelled() {
+                j.create_cancelled_result()
+            } else {
+                j.create_skipped_result()
+            };
+            progress.job_finished(&job_result);
+        }
+    }
+    progress.finished();
+}
+
+type Task = Box<dyn FnOnce(usize) + Send>;
+
+#[derive(Default)]
+struct PoolState {
+    tasks: VecDeque<Task>,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct PoolQueue {
+    state: Mutex<PoolState>,
+    changed: Condvar,
+}
+
+/// The number of batches of a run which are not done yet.
+#[derive(Default)]
+struct Remaining {
+    count: Mutex<usize>,
+    done: Condvar,
+}
+
+/// A fixed set of threads which run the batches of many runs, e.g. the sessions submitted to
+/// `hugin serve`.
+///
+/// The batches are run in the order of submission, so the threads left idle at the end of a run
+/// take the batches of the next one. The threads are stopped when the pool is dropped.
+pub struct JobPool {
+    queue: Arc<PoolQueue>,
+    work_dirs: Arc<WorkDirs>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// The batches submitted to a `JobPool` by `JobPool::submit`.
+pub struct PoolRun<P: ?Sized> {
+    remaining: Arc<Remaining>,
+    progress: Arc<P>,
+}
+
+impl<P> PoolRun<P>
+where
+    P: Progress + ?Sized,
+{
+    /// Blocks until every job of the run has got a result.
+    pub fn wait(self) {
+        let count = self.remaining.count.lock().unwrap();
+        drop(self.remaining.done.wait_while(count, |c| *c > 0).unwrap());
+        self.progress.finished();
+    }
+}
+
+/// Runs the batch in a thread of the pool, or cancels its jobs once the run is cancelled.
+fn run_pooled_batch<R, P>(
+    thread: usize,
+    batch: &[Job],
+    runner: &R,
+    work_dirs: &WorkDirs,
+    progress: &P,
+) where
+    R: Runner + ?Sized,
+    P: Progress + ?Sized,
+{
+    if cancel::is_cancelled() {
+        for j in batch {
+            progress.job_finished(&j.create_cancelled_result());
+        }
+        return;
+    }
+    progress.batch_started(thread, batch);
+    for job_result in run_batch(runner, batch) {
+        let kept = work_dirs.take_kept(job_result.get_job());
+        progress.job_finished(&job_result.with_workdirs(kept));
+    }
+    progress.thread_finished(thread);
+}
+
+impl JobPool {
+    pub fn new(number_of_threads: usize, work_dirs: Arc<WorkDirs>) -> Self {
+        let queue = Arc::new(PoolQueue::default());
+        let threads = (0..number_of_threads.max(1))
+            .map(|i| {
+                let queue = queue.clone();
+                thread::spawn(move || loop {
+                    let task = {
+                        let state = queue.state.lock().unwrap();
+                        let mut state = queue
+                            .changed
+                            .wait_while(state, |s| s.tasks.is_empty() && !s.closed)
+                            .unwrap();
+                        match state.tasks.pop_front() {
+                            Some(t) => t,
+                            None => break,
+                        }
+                    };
+                    queue.changed.notify_all();
+                    task(i);
+                })
+            })
+            .collect();
+        JobPool {
+            queue,
+            work_dirs,
+            threads,
+        }
+    }
+
+    pub fn get_number_of_threads(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Queues the batches behind the ones already submitted, and returns the run to wait for.
+    ///
+    /// The results are passed to `progress` like `run_jobs` does.
+    pub fn submit<R, P>(
+        &self,
+        batches: Vec<Vec<Job>>,
+        runner: Arc<R>,
+        progress: Arc<P>,
+    ) -> PoolRun<P>
+    where
+        R: Runner + Sync + Send + ?Sized + 'static,
+        P: Progress + Sync + Send + ?Sized + 'static,
+    {
+        let number_of_jobs: usize = batches.iter().map(|b| b.len()).sum();
+        progress.started(number_of_jobs, self.get_number_of_threads());
+        let remaining = Arc::new(Remaining {
+            count: Mutex::new(batches.len()),
+            done: Condvar::new(),
+        });
+
+        let mut state = self.queue.state.lock().unwrap();
+        for batch in batches {
+            let runner = runner.clone();
+            let work_dirs = self.work_dirs.clone();
+            let progress = progress.clone();
+            let remaining = remaining.clone();
+            state.tasks.push_back(Box::new(move |thread| {
+                let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                    run_pooled_batch(
+                        thread,
+                        &batch,
+                        runner.as_ref(),
+                        &work_dirs,
+                        progress.as_ref(),
+                    )
+                }));
+                if let Err(e) = res {
+                    error!("A thread failed with error: {:?}", e);
+                }
+                // NOTE: The references are dropped first so that the progress is not shared any
+                //       more once the run is done.
+                drop(progress);
+                drop(runner);
+                *remaining.count.lock().unwrap() -= 1;
+                remaining.done.notify_all();
+            }));
+        }
+        self.queue.changed.notify_all();
+        PoolRun {
+            remaining,
+            progress,
+        }
+    }
+
+    /// Blocks until the threads have taken all the submitted batches, i.e. until a thread is about
+    /// to be idle.
+    pub fn wait_until_taken(&self) {
+        let state = self.queue.state.lock().unwrap();
+        drop(
+            self.queue
+                .changed
+                .wait_while(state, |s| !s.tasks.is_empty())
+                .unwrap(),
+        );
+    }
+}
+
+impl Drop for JobPool {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().closed = true;
+        self.queue.changed.notify_all();
+        for t in self.threads.drain(..) {
+            if let Err(e) = t.join() {
+                error!("A thread failed with error: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::sync::{Arc, Mutex};
+
+    use crate::clone_pair::ClonePair;
+    use crate::error::RunnerProcessFailedError;
+    use crate::job::{test_job, Job, JobResult, JobStatus};
+    use crate::runner::workdir::{KeepWorkDirs, WorkDirs};
+    use crate::runner::Runner;
+    use crate::scheduler::{run_jobs, JobPool, Progress};
+
+    /// Fails the jobs on the library named "Broken".
+    struct StubRunner;
+
+    impl Runner for StubRunner {
+        fn run_job(&self, job: Job) -> Result<Vec<ClonePair>, Box<dyn Error>> {
+            if job.get_library_info().get_name() == "Broken" {
+                Err(Box::new(RunnerProcessFailedError::new(1)))
+            } else {
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Collector {
+        started: Mutex<Option<(usize, usize)>>,
+        results: Mutex<Vec<JobResult>>,
+        finished: Mutex<bool>,
+    }
+
+    impl Progress for Collector {
+        fn started(&self, number_of_jobs: usize, number_of_threads: usize) {
+            *self.started.lock().unwrap() = Some((number_of_jobs, number_of_threads));
+        }
+
+        fn job_finished(&self, result: &JobResult) {
+            self.results.lock().unwrap().push(result.clone());
+        }
+
+        fn finished(&self) {
+            *self.finished.lock().unwrap() = true;
+        }
+    }
+
+    #[test]
+    fn test_run_jobs() {
+        let batches = vec![
+            vec![
+                test_job("Example/Example.ino").with_library("A"),
+                test_job("Example/Example.ino").with_library("B"),
+            ],
+            vec![test_job("Example/Example.ino").with_library("Broken")],
+        ];
+        let work_dirs = Arc::new(WorkDirs::new(None, KeepWorkDirs::Never).unwrap());
+        let progress = Arc::new(Collector::default());
+        run_jobs(
+            batches,
+            Arc::new(StubRunner),
+            work_dirs,
+            progress.clone(),
+            4,
+        );
+
+        assert_eq!(*progress.started.lock().unwrap(), Some((3, 2)));
+        assert!(*progress.finished.lock().unwrap());
+        let mut statuses: Vec<(String, JobStatus)> = progress
+            .results
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    String::from(r.get_job().get_library_info().get_name()),
+                    r.get_status(),
+                )
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            statuses,
+            vec![
+                (String::from("A"), JobStatus::Ok),
+                (String::from("B"), JobStatus::Ok),
+                (String::from("Broken"), JobStatus::Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_job_pool() {
+        let work_dirs = Arc::new(WorkDirs::new(None, KeepWorkDirs::Never).unwrap());
+        let pool = JobPool::new(2, work_dirs);
+        let runner = Arc::new(StubRunner);
+        let first = Arc::new(Collector::default());
+        let second = Arc::new(Collector::default());
+        let first_run = pool.submit(
+            vec![
+                vec![test_job("Example/Example.ino").with_library("A")],
+                vec![test_job("Example/Example.ino").with_library("Broken")],
+                vec![test_job("Example/Example.ino").with_library("B")],
+            ],
+            runner.clone(),
+            first.clone(),
+        );
+        let second_run = pool.submit(
+            vec![vec![test_job("Example/Example.ino").with_library("C")]],
+            runner,
+            second.clone(),
+        );
+        second_run.wait();
+        first_run.wait();
+        pool.wait_until_taken();
+
+        assert_eq!(*first.started.lock().unwrap(), Some((3, 2)));
+        assert!(*first.finished.lock().unwrap() && *second.finished.lock().unwrap());
+        let failed = first
+            .results
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.get_status() == JobStatus::Failed)
+            .count();
+        assert_eq!((first.results.lock().unwrap().len(), failed), (3, 1));
+        assert_eq!(
+            second.results.lock().unwrap()[0].get_status(),
+            JobStatus::Ok
+        );
+        // The progress is not shared with the pool any more once the run is done.
+        assert_eq!(Arc::strong_count(&first), 1);
+    }
+}
@@ -1,4 +1,6 @@
 use std::error::Error;
+use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 use serde_derive::Deserialize;
@@ -10,6 +12,14 @@ pub struct Session {
 }
 
 impl Session {
+    /// Loads `session.toml` in the session directory.
+    pub fn load(session_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(session_path.join("session.toml"))?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(toml::from_str(contents.as_str())?)
+    }
+
     pub fn get_absolute_project_path(
         &self,
         session_path: &Path,
@@ -24,4 +34,15 @@ impl Session {
             .join(&self.jobs_path)
             .canonicalize()?)
     }
+
+    /// Returns the paths of the job files in the jobs directory, sorted by name.
+    pub fn get_job_files(&self, session_path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut res = Vec::new();
+        for entry in self.get_absolute_jobs_path(session_path)?.read_dir()? {
+            res.push(entry?.path());
+        }
+        // NOTE: `read_dir` returns the entries in an order which depends on the file system.
+        res.sort();
+        Ok(res)
+    }
 }
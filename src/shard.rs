@@ -0,0 +1,100 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::InvalidShardError;
+use crate::hash::StableHasher;
+use crate::job::Job;
+
+/// One of `count` disjoint parts of a session, which can be run on different hosts.
+///
+/// The jobs are assigned by a stable hash of the project, the library and the example sketch,
+/// so every host selects the same jobs for a shard without talking to the others.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shard {
+    /// Starts from 1.
+    index: u32,
+    count: u32,
+}
+
+impl Shard {
+    pub fn new(index: u32, count: u32) -> Result<Self, InvalidShardError> {
+        if index == 0 || index > count {
+            return Err(InvalidShardError::new(&format!("{}/{}", index, count)));
+        }
+        Ok(Shard { index, count })
+    }
+
+    /// Returns the shard of the job among `count` shards.
+    pub fn of(job: &Job, count: u32) -> Self {
+        let mut hasher = StableHasher::new();
+        hasher.update_str(job.get_project().get_location());
+        hasher.update_str(job.get_library_info().get_name());
+        hasher.update_str(&job.get_library_info().get_version().to_string());
+        hasher.update_str(job.get_example_sketch().get_location());
+        Shard {
+            index: (hasher.finish() % count as u128) as u32 + 1,
+            count,
+        }
+    }
+
+    pub fn contains(&self, job: &Job) -> bool {
+        Shard::of(job, self.count) == *self
+    }
+}
+
+impl FromStr for Shard {
+    type Err = InvalidShardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s.split_once('/').ok_or_else(|| InvalidShardError::new(s))?;
+        match (u32::from_str(index.trim()), u32::from_str(count.trim())) {
+            (Ok(index), Ok(count)) => {
+                Shard::new(index, count).map_err(|_| InvalidShardError::new(s))
+            }
+            _ => Err(InvalidShardError::new(s)),
+        }
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use crate::job::{test_job, Job};
+    use crate::shard::Shard;
+
+    #[test]
+    fn test_parse_shard() {
+        assert_eq!(Shard::from_str("2/3").unwrap(), Shard::new(2, 3).unwrap());
+        assert_eq!(Shard::from_str("2/3").unwrap().to_string(), "2/3");
+        for s in &["0/3", "4/3", "1/0", "1", "a/3", "1/3/5"] {
+            assert!(Shard::from_str(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_shards_partition_jobs() {
+        let jobs: Vec<Job> = (0..50)
+            .map(|i| {
+                test_job(&format!("E{0}/E{0}.ino", i)).with_library(&format!("Library{}", i % 7))
+            })
+            .collect();
+        let shards: Vec<Shard> = (1..=4).map(|i| Shard::new(i, 4).unwrap()).collect();
+        for job in &jobs {
+            assert_eq!(shards.iter().filter(|s| s.contains(job)).count(), 1);
+        }
+        // Every shard gets some of the jobs.
+        assert!(shards.iter().all(|s| jobs.iter().any(|j| s.contains(j))));
+        // The assignment doesn't depend on the build (see `StableHasher`).
+        assert_eq!(
+            Shard::of(&test_job("Example/Example.ino"), 1000).to_string(),
+            "878/1000"
+        );
+    }
+}
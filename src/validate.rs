@@ -0,0 +1,406 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs::File;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use zip::ZipArchive;
+
+use crate::config::Config;
+use crate::job::Job;
+use crate::runner;
+use crate::runner::archive::ArchiveCache;
+use crate::session::Session;
+
+/// What a problem was found in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProblemKind {
+    Session,
+    JobFile,
+    Project,
+    LibraryArchive,
+    ArchiveRoot,
+    ExampleSketch,
+    Detector,
+}
+
+impl ProblemKind {
+    fn get_name(&self) -> &'static str {
+        match self {
+            ProblemKind::Session => "session",
+            ProblemKind::JobFile => "job file",
+            ProblemKind::Project => "project source",
+            ProblemKind::LibraryArchive => "library archive",
+            ProblemKind::ArchiveRoot => "archive root",
+            ProblemKind::ExampleSketch => "example sketch",
+            ProblemKind::Detector => "detector",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Problem {
+    kind: ProblemKind,
+    /// The file or the program which has the problem.
+    subject: String,
+    message: String,
+    /// What to do about the problem.
+    hint: String,
+}
+
+impl Problem {
+    fn new(kind: ProblemKind, subject: &Path, message: &str, hint: &str) -> Self {
+        Problem {
+            kind,
+            subject: String::from(subject.to_str().unwrap()),
+            message: String::from(message),
+            hint: String::from(hint),
+        }
+    }
+}
+
+/// The problems found in a session before running it.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    number_of_jobs: usize,
+    number_of_valid_jobs: usize,
+    problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn print(&self) {
+        for problem in &self.problems {
+            println!(
+                "[{}] {}: {}",
+                problem.kind.get_name(),
+                problem.subject,
+                problem.message
+            );
+            println!("    hint: {}", problem.hint);
+        }
+        let mut counts: BTreeMap<ProblemKind, usize> = BTreeMap::new();
+        for problem in &self.problems {
+            *counts.entry(problem.kind).or_insert(0) += 1;
+        }
+        println!(
+            "Checked {} job(s): {} valid, {} with problems.",
+            self.number_of_jobs,
+            self.number_of_valid_jobs,
+            self.number_of_jobs - self.number_of_valid_jobs
+        );
+        for (kind, count) in counts {
+            println!("  {}: {} problem(s)", kind.get_name(), count);
+        }
+    }
+}
+
+/// The entries of the library archives, read once for all jobs.
+struct ArchiveIndex {
+    entries: HashMap<PathBuf, Result<HashSet<String>, String>>,
+}
+
+impl ArchiveIndex {
+    fn get_entries(&mut self, archive_path: &Path) -> &Result<HashSet<String>, String> {
+        self.entries
+            .entry(PathBuf::from(archive_path))
+            .or_insert_with(|| {
+                let archive = File::open(archive_path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|f| ZipArchive::new(f).map_err(|e| e.to_string()))?;
+                Ok(archive.file_names().map(String::from).collect())
+            })
+    }
+}
+
+fn find_program(program: &str) -> Option<PathBuf> {
+    let is_executable = |p: &Path| {
+        p.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    };
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return if is_executable(&path) {
+            Some(path)
+        } else {
+            None
+        };
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|d| d.join(program))
+        .find(|p| is_executable(p))
+}
+
+fn validate_job(
+    job: &Job,
+    job_file: &Path,
+    project_path: &Path,
+    database_path: &Path,
+    archives: &mut ArchiveIndex,
+) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if let Err(e) = job.get_project().get_location_from(project_path) {
+        problems.push(Problem::new(
+            ProblemKind::Project,
+            job_file,
+            &format!("`{}`: {}", job.get_project().get_location(), e),
+            "Check `project.location` and `project_path` in `session.toml`.",
+        ));
+    }
+    let archive_path = match job.get_library_info().get_absolute_location(database_path) {
+        Ok(p) => p,
+        Err(e) => {
+            problems.push(Problem::new(
+                ProblemKind::LibraryArchive,
+                job_file,
+                &format!("`{}`: {}", job.get_library_info().get_location(), e),
+                "Fetch the library with Munin or check `munin_database_root` in the configuration.",
+            ));
+            return problems;
+        }
+    };
+    let entries = match archives.get_entries(&archive_path) {
+        Ok(e) => e,
+        Err(e) => {
+            problems.push(Problem::new(
+                ProblemKind::LibraryArchive,
+                &archive_path,
+                &format!("Could not read the archive: {}", e),
+                "Download the library archive again.",
+            ));
+            return problems;
+        }
+    };
+    let archive_root = format!("{}/", job.get_library_info().get_archive_root());
+    if !entries.iter().any(|e| e.starts_with(&archive_root)) {
+        let mut roots: Vec<&str> = entries
+            .iter()
+            .filter_map(|e| e.split('/').next())
+            .collect::<HashSet<&str>>()
+            .into_iter()
+            .collect();
+        roots.sort_unstable();
+        problems.push(Problem::new(
+            ProblemKind::ArchiveRoot,
+            job_file,
+            &format!(
+                "`{}` is not in `{}`.",
+                job.get_library_info().get_archive_root(),
+                archive_path.to_str().unwrap()
+            ),
+            &format!(
+                "Set `library_info.archive_root` to one of: {}",
+                roots.join(", ")
+            ),
+        ));
+        return problems;
+    }
+    let example_entry = ArchiveCache::get_example_entry_path(job);
+    if !entries.contains(example_entry.to_str().unwrap()) {
+        problems.push(Problem::new(
+            ProblemKind::ExampleSketch,
+            job_file,
+            &format!(
+                "`{}` is not in `{}`.",
+                example_entry.to_str().unwrap(),
+                archive_path.to_str().unwrap()
+            ),
+            "Check `example_sketch.location`, which is relative to the `examples` directory.",
+        ));
+    }
+    problems
+}
+
+/// Checks the session, its jobs and the detector without running any job.
+pub fn validate_session(session_path: &Path, config: &Config) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    match runner::get_detector_programs(config) {
+        Ok(programs) => {
+            for program in programs {
+                if find_program(&program).is_none() {
+                    report.problems.push(Problem::new(
+                        ProblemKind::Detector,
+                        Path::new(&program),
+                        "The program is not found or not executable.",
+                        "Install the detector or fix its path in the configuration.",
+                    ));
+                }
+            }
+        }
+        Err(e) => report.problems.push(Problem::new(
+            ProblemKind::Detector,
+            Path::new(&format!("{:?}", config.get_clone_detector_kind())),
+            &e.to_string(),
+            "Fix `clone_detector_config` in the configuration (see `samples/config.toml`).",
+        )),
+    }
+
+    let session_file = session_path.join("session.toml");
+    let session = match Session::load(session_path) {
+        Ok(s) => s,
+        Err(e) => {
+            report.problems.push(Problem::new(
+                ProblemKind::Session,
+                &session_file,
+                &e.to_string(),
+                "Generate the session again with Munin.",
+            ));
+            return report;
+        }
+    };
+    let project_path = match session.get_absolute_project_path(session_path) {
+        Ok(p) => p,
+        Err(e) => {
+            report.problems.push(Problem::new(
+                ProblemKind::Session,
+                &session_file,
+                &format!("The project directory: {}", e),
+                "Check `project_path` in `session.toml`.",
+            ));
+            return report;
+        }
+    };
+    let job_files = match session.get_job_files(session_path) {
+        Ok(f) => f,
+        Err(e) => {
+            report.problems.push(Problem::new(
+                ProblemKind::Session,
+                &session_file,
+                &format!("The jobs directory: {}", e),
+                "Check `jobs_path` in `session.toml`.",
+            ));
+            return report;
+        }
+    };
+    let database_path = match config.get_absolute_database_root_path() {
+        Ok(p) => p,
+        Err(e) => {
+            report.problems.push(Problem::new(
+                ProblemKind::LibraryArchive,
+                Path::new("munin_database_root"),
+                &e.to_string(),
+                "Check `munin_database_root` in the configuration.",
+            ));
+            return report;
+        }
+    };
+
+    let mut archives = ArchiveIndex {
+        entries: HashMap::new(),
+    };
+    for job_file in job_files {
+        report.number_of_jobs += 1;
+        let job = match Job::load(&job_file) {
+            Ok(j) => j,
+            Err(e) => {
+                report.problems.push(Problem::new(
+                    ProblemKind::JobFile,
+                    &job_file,
+                    &e.to_string(),
+                    "Remove the file or generate the session again with Munin.",
+                ));
+                continue;
+            }
+        };
+        let problems = validate_job(
+            &job,
+            &job_file,
+            &project_path,
+            &database_path,
+            &mut archives,
+        );
+        if problems.is_empty() {
+            report.number_of_valid_jobs += 1;
+        }
+        report.problems.extend(problems);
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use crate::config::Config;
+    use crate::job::{test_job, Job};
+    use crate::validate::{validate_session, ProblemKind};
+
+    fn write_job(path: &std::path::Path, job: &Job) {
+        fs::write(path, toml::to_string(job).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_validate_session() {
+        let root = tempfile::tempdir().unwrap();
+        let library_path = root.path().join("munin/libraries/Library/1.0.0");
+        fs::create_dir_all(&library_path).unwrap();
+        let mut zip = ZipWriter::new(File::create(library_path.join("Library-1.0.0.zip")).unwrap());
+        zip.start_file(
+            "Library-1.0.0/examples/Example/Example.ino",
+            FileOptions::default(),
+        )
+        .unwrap();
+        write!(zip, "void setup() {{}}").unwrap();
+        zip.finish().unwrap();
+
+        let session_path = root.path().join("session");
+        fs::create_dir_all(session_path.join("project")).unwrap();
+        fs::create_dir_all(session_path.join("jobs")).unwrap();
+        fs::write(
+            session_path.join("session.toml"),
+            "project_path = \"project\"\njobs_path = \"jobs\"\n",
+        )
+        .unwrap();
+        File::create(session_path.join("project/MyProject.ino")).unwrap();
+        let jobs_path = session_path.join("jobs");
+        write_job(&jobs_path.join("ok.toml"), &test_job("Example/Example.ino"));
+        write_job(
+            &jobs_path.join("root.toml"),
+            &test_job("Example/Example.ino").with_archive_root("Library"),
+        );
+        write_job(
+            &jobs_path.join("example.toml"),
+            &test_job("Missing/Missing.ino"),
+        );
+        fs::write(jobs_path.join("broken.toml"), "[project").unwrap();
+
+        let config: Config = toml::from_str(&format!(
+            r#"
+munin_database_root = "{}"
+clone_detector_kind = "Native"
+number_of_jobs = 1
+
+[clone_detector_config]
+token_length = "20"
+"#,
+            root.path().join("munin").to_str().unwrap()
+        ))
+        .unwrap();
+        let report = validate_session(&session_path, &config);
+        assert_eq!(report.number_of_jobs, 4);
+        assert_eq!(report.number_of_valid_jobs, 1);
+        let mut kinds: Vec<ProblemKind> = report.problems.iter().map(|p| p.kind).collect();
+        kinds.sort();
+        assert_eq!(
+            kinds,
+            vec![
+                ProblemKind::JobFile,
+                ProblemKind::ArchiveRoot,
+                ProblemKind::ExampleSketch
+            ]
+        );
+        assert!(report.problems[..]
+            .iter()
+            .any(|p| p.hint.contains("Library-1.0.0")));
+    }
+}
@@ -0,0 +1,54 @@
+import java.io.BufferedReader;
+import java.io.FileDescriptor;
+import java.io.FileOutputStream;
+import java.io.InputStreamReader;
+import java.io.PrintStream;
+import java.lang.reflect.InvocationTargetException;
+import java.lang.reflect.Method;
+import java.nio.charset.StandardCharsets;
+
+/**
+ * A long-lived launcher which runs CCFinderSW repeatedly in a single JVM.
+ *
+ * Protocol (one line per message, UTF-8):
+ *   worker -> Hugin: "READY" once the main class is loaded.
+ *   Hugin -> worker: the command line arguments of CCFinderSW separated by tabs.
+ *   worker -> Hugin: "DONE" or "FAILED <message>".
+ *
+ * The output of CCFinderSW itself is redirected to stderr so that it doesn't disturb the protocol.
+ */
+public final class HuginWorker {
+    public static void main(String[] args) throws Exception {
+        if (args.length != 1) {
+            System.err.println("usage: HuginWorker MAIN_CLASS");
+            System.exit(2);
+        }
+        Method entry = Class.forName(args[0]).getMethod("main", String[].class);
+
+        PrintStream protocol =
+                new PrintStream(new FileOutputStream(FileDescriptor.out), true, "UTF-8");
+        System.setOut(System.err);
+        BufferedReader requests =
+                new BufferedReader(new InputStreamReader(System.in, StandardCharsets.UTF_8));
+
+        protocol.println("READY");
+        String line;
+        while ((line = requests.readLine()) != null) {
+            if (line.isEmpty()) {
+                continue;
+            }
+            try {
+                entry.invoke(null, (Object) line.split("\t", -1));
+                protocol.println("DONE");
+            } catch (InvocationTargetException e) {
+                protocol.println("FAILED " + describe(e.getCause()));
+            } catch (Throwable e) {
+                protocol.println("FAILED " + describe(e));
+            }
+        }
+    }
+
+    private static String describe(Throwable t) {
+        return String.valueOf(t).replace('\n', ' ').replace('\r', ' ');
+    }
+}
@@ -0,0 +1,65 @@
+#!/usr/bin/env python3
+"""A Hugin detector plugin which reports the runs of identical lines.
+
+See README.adoc for the protocol.
+"""
+
+import json
+import sys
+
+PROTOCOL_VERSION = 1
+
+
+def send(message):
+    print(json.dumps(message), flush=True)
+
+
+def read_lines(path):
+    with open(path, encoding="utf-8", errors="replace") as f:
+        return [line.strip() for line in f]
+
+
+def find_clones(project, example, min_length):
+    pairs = []
+    for i in range(len(project)):
+        for j in range(len(example)):
+            # Report only the longest runs.
+            if i > 0 and j > 0 and project[i - 1] == example[j - 1] and project[i - 1]:
+                continue
+            n = 0
+            while (i + n < len(project) and j + n < len(example)
+                   and project[i + n] and project[i + n] == example[j + n]):
+                n += 1
+            if n >= min_length:
+                pairs.append({
+                    "project": {"start": {"lines": i + 1, "columns": 0},
+                                "end": {"lines": i + n, "columns": 0}},
+                    "example_sketch": {"start": {"lines": j + 1, "columns": 0},
+                                       "end": {"lines": j + n, "columns": 0}},
+                    "scores": {"project_part": 100.0, "example_sketch_part": 100.0},
+                })
+    return pairs
+
+
+def main():
+    min_length = 5
+    for line in sys.stdin:
+        request = json.loads(line)
+        if request["type"] == "hello":
+            if request["protocol_version"] != PROTOCOL_VERSION:
+                sys.exit("unsupported protocol version: {}".format(request["protocol_version"]))
+            min_length = int(request["settings"].get("min_length", min_length))
+            send({"type": "ready", "protocol_version": PROTOCOL_VERSION, "name": "example"})
+        elif request["type"] == "job":
+            try:
+                pairs = find_clones(read_lines(request["project_file"]),
+                                    read_lines(request["example_file"]), min_length)
+                send({"type": "result", "id": request["id"], "clone_pairs": pairs})
+            except OSError as e:
+                send({"type": "error", "id": request["id"], "message": str(e)})
+        elif request["type"] == "shutdown":
+            break
+
+
+if __name__ == "__main__":
+    main()